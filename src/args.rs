@@ -0,0 +1,219 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use cabinet::execute::Options;
+use cabinet::util::action::Action;
+use cabinet::util::config::Config;
+use cabinet::util::conflict::OnConflict;
+use cabinet::util::walk::{Entries, Symlinks, WalkOptions};
+use clap::{Arg, ArgMatches, Command};
+
+/// Get the walk options from the global options
+pub fn walk_options(args: &ArgMatches) -> Result<WalkOptions, String> {
+    let min_depth = args.get_one::<usize>("min-depth").copied();
+    let max_depth = args.get_one::<usize>("max-depth").copied();
+
+    if min_depth == Some(0) || max_depth == Some(0) {
+        return Err(
+            "The minimum and maximum depth must be at least 1 (the folder being sorted)"
+                .to_string(),
+        );
+    }
+    if let (Some(min), Some(max)) = (min_depth, max_depth) {
+        if min > max {
+            return Err(format!(
+                "The minimum depth ({min}) cannot be greater than the maximum depth ({max})"
+            ));
+        }
+    }
+
+    // Depth limits only make sense when walking subfolders
+    let recursive = args.get_flag("recursive") || min_depth.is_some() || max_depth.is_some();
+
+    Ok(WalkOptions {
+        recursive,
+        min_depth: min_depth.unwrap_or(1),
+        max_depth: if recursive { max_depth } else { Some(1) },
+        output: args.get_one::<String>("output").cloned(),
+        entries: match args.get_one::<String>("entries").map(String::as_str) {
+            Some("dirs") => Entries::Dirs,
+            Some("all") => Entries::All,
+            _ => Entries::Files,
+        },
+        symlinks: match args.get_one::<String>("symlinks").map(String::as_str) {
+            Some("follow") => Symlinks::Follow,
+            Some("move-link") => Symlinks::MoveLink,
+            _ => Symlinks::Skip,
+        },
+    })
+}
+
+/// Get the options for sorting files from the global options
+pub fn executor_options(args: &ArgMatches) -> Options {
+    Options {
+        action: match args.get_one::<String>("action").map(String::as_str) {
+            Some("copy") => Action::Copy,
+            Some("hardlink") => Action::Hardlink,
+            Some("symlink") => Action::Symlink,
+            Some("reflink") => Action::Reflink,
+            _ => Action::Move,
+        },
+        on_conflict: match args.get_one::<String>("on-conflict").map(String::as_str) {
+            Some("skip") => OnConflict::Skip,
+            Some("overwrite") => OnConflict::Overwrite,
+            Some("keep-newer") => OnConflict::KeepNewer,
+            Some("keep-larger") => OnConflict::KeepLarger,
+            Some("dedupe") => OnConflict::Dedupe,
+            _ => OnConflict::Rename,
+        },
+        verify: args.get_flag("verify"),
+        dry_run: args.get_flag("dry-run"),
+    }
+}
+
+/// Get the value of `--config` from the raw command line, if given
+pub fn config_arg(args: &[OsString]) -> Option<PathBuf> {
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == "--config" {
+            return iter.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.to_str().and_then(|a| a.strip_prefix("--config=")) {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+/// Expand a command alias and add the default options for the command to the raw command line.
+///
+/// The defaults are inserted straight after the command name. Options given on the command line
+/// replace their default rather than being added to it, which matters for options that can be
+/// given more than once.
+pub fn expand_args(
+    cli: &Command,
+    mut args: Vec<OsString>,
+    config: &Config,
+) -> Result<Vec<OsString>, String> {
+    let mut idx = match command_index(cli, &args) {
+        Some(i) => i,
+        None => return Ok(args),
+    };
+
+    // Built-in commands cannot be overridden by an alias
+    let name = args[idx].to_string_lossy().to_string();
+    if cli.find_subcommand(&name).is_none() {
+        if let Some(alias) = config.alias.get(&name) {
+            let expanded = alias.args().into_iter().map(OsString::from);
+            args.splice(idx..=idx, expanded);
+            idx = match command_index(cli, &args) {
+                Some(i) => i,
+                None => return Ok(args),
+            };
+        }
+    }
+
+    let name = args[idx].to_string_lossy().to_string();
+    if let Some(cmd) = cli.find_subcommand(&name) {
+        let given = given_options(cli, cmd, &args[1..]);
+        let defaults = config.default_args(cmd.get_name(), |key| {
+            find_arg(cli, cmd, |a| {
+                a.get_long() == Some(key) || a.get_all_aliases().is_some_and(|l| l.contains(&key))
+            })
+            .is_some_and(|a| given.contains(a.get_id().as_str()))
+        })?;
+        args.splice(idx + 1..idx + 1, defaults.into_iter().map(OsString::from));
+    }
+    Ok(args)
+}
+
+/// Find the position of the command name in the raw command line, skipping over any global
+/// options (and their values) that come before it
+fn command_index(cli: &Command, args: &[OsString]) -> Option<usize> {
+    let takes_value = |arg: &str| {
+        cli.get_arguments().any(|a| {
+            a.get_action().takes_values()
+                && (a.get_long().is_some_and(|l| arg == format!("--{l}"))
+                    || a.get_short().is_some_and(|s| arg == format!("-{s}")))
+        })
+    };
+
+    let mut idx = 1;
+    while idx < args.len() {
+        let arg = args[idx].to_string_lossy();
+        if arg == "--" {
+            return None;
+        }
+        if !arg.starts_with('-') {
+            return Some(idx);
+        }
+        if takes_value(&arg) {
+            idx += 1;
+        }
+        idx += 1;
+    }
+    None
+}
+
+/// Find an option of the command, or one of the global options
+fn find_arg<'a>(
+    cli: &'a Command,
+    cmd: &'a Command,
+    pred: impl Fn(&Arg) -> bool,
+) -> Option<&'a Arg> {
+    cmd.get_arguments()
+        .chain(cli.get_arguments())
+        .find(|a| pred(a))
+}
+
+/// Get the ids of the options that appear in the raw command line
+fn given_options(cli: &Command, cmd: &Command, args: &[OsString]) -> HashSet<String> {
+    let mut given = HashSet::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let arg = arg.to_string_lossy();
+        if arg == "--" {
+            break;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, has_value) = match long.split_once('=') {
+                Some((name, _)) => (name, true),
+                None => (long, false),
+            };
+            let found = find_arg(cli, cmd, |a| {
+                a.get_long() == Some(name) || a.get_all_aliases().is_some_and(|l| l.contains(&name))
+            });
+            if let Some(a) = found {
+                given.insert(a.get_id().to_string());
+                if a.get_action().takes_values() && !has_value {
+                    iter.next();
+                }
+            }
+        } else if let Some(shorts) = arg.strip_prefix('-') {
+            // Several short flags can be combined, e.g. `-ry`, and the last one may take a value
+            for (idx, c) in shorts.char_indices() {
+                let found = find_arg(cli, cmd, |a| {
+                    a.get_short() == Some(c)
+                        || a.get_all_short_aliases().is_some_and(|l| l.contains(&c))
+                });
+                let a = match found {
+                    Some(a) => a,
+                    None => break,
+                };
+                given.insert(a.get_id().to_string());
+                if a.get_action().takes_values() {
+                    if idx + c.len_utf8() == shorts.len() {
+                        iter.next();
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    given
+}
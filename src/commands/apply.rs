@@ -0,0 +1,154 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use cabinet::filter::Filter;
+use cabinet::sort::rules;
+use cabinet::util::walk::{remove_nested, Entry};
+use cabinet::Plan;
+use clap::{Arg, ArgMatches, Command};
+
+use crate::context::Context;
+use crate::ui::{self, get_current_path, get_path};
+
+pub fn cli() -> Command {
+    Command::new("apply")
+        .about("Sort files into folders using the rules in a rules file")
+        .args([Arg::new("template")
+            .short('t')
+            .long("template")
+            .help("The path you are using is a predefined one (e.g. 'downloads' for your downloads folder)")
+            .action(clap::ArgAction::SetTrue)])
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new("rules")
+                .action(clap::ArgAction::Set)
+                .value_name("RULES")
+                .help("The rules file (TOML) to use")
+                .required(true),
+        )
+        .arg(
+            Arg::new("path")
+                .action(clap::ArgAction::Set)
+                .value_name("PATH")
+                .required(true),
+        )
+}
+
+pub fn exec(args: &ArgMatches, ctx: &mut Context) {
+    let rules_file = PathBuf::from(args.get_one::<String>("rules").unwrap());
+    let rules = match rules::load(&rules_file) {
+        Ok(r) => r,
+        Err(error) => {
+            println!("ERROR: {error}");
+            return;
+        }
+    };
+
+    let mut path: Option<PathBuf> = None;
+    let use_template = args.get_flag("template");
+
+    if let Some(p) = args.get_one::<String>("path") {
+        path = get_path(p, use_template, &ctx.config);
+    }
+    if path.is_none() {
+        println!("ERROR: The path is invalid");
+        return;
+    }
+
+    if args.get_one::<String>("output").is_some() {
+        println!("NOTE: Setting a custom output folder is not possible when applying rules, as each rule has its own destination");
+    }
+
+    // Never re-sort files that are already in one of the destinations
+    let destinations: Vec<PathBuf> = rules
+        .iter()
+        .map(|r| path.as_ref().unwrap().join(&r.destination))
+        .collect();
+    let dir = ui::walk(path.as_ref().unwrap(), &destinations, ctx);
+    let parent = get_current_path(path);
+
+    // The files matched by each rule, in the same order as the rules
+    let mut matched: Vec<Vec<Entry>> = rules.iter().map(|_| vec![]).collect();
+    let mut total = 0;
+
+    // Anything inside a folder that is moved by a rule is moved along with it
+    let dir: Vec<Entry> = dir
+        .into_iter()
+        .filter(|e| rules.iter().any(|r| r.matches(e)))
+        .collect();
+    for item in remove_nested(dir) {
+        // First match wins
+        if let Some(idx) = rules.iter().position(|r| r.matches(&item)) {
+            matched[idx].push(item);
+            total += 1;
+        }
+    }
+
+    if total == 0 {
+        println!("There are no files to sort that match the given rules");
+        return;
+    }
+    println!(
+        "Found {} files that match {} rules",
+        &total,
+        matched.iter().filter(|m| !m.is_empty()).count()
+    );
+    for (rule, files) in rules.iter().zip(&matched) {
+        println!(
+            "  {}: {} files -> {}",
+            &rule.name,
+            files.len(),
+            rule.destination.display()
+        );
+    }
+
+    let mut plan = Plan::default();
+    for (rule, files) in rules.iter().zip(&matched) {
+        if files.is_empty() {
+            continue;
+        }
+        let full_path = match create_destination(&parent, &rule.destination, ctx) {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+        for file in files {
+            plan.push(file, &full_path);
+        }
+    }
+
+    ui::run_plan(&plan, total, ctx);
+}
+
+/// Create the destination folder of a rule, along with any missing parent folders
+fn create_destination(
+    parent: &Path,
+    destination: &Path,
+    ctx: &mut Context,
+) -> Result<PathBuf, io::Error> {
+    // Joining with an absolute destination replaces the parent
+    let full_path = parent.join(destination);
+
+    let mut missing: Vec<&Path> = vec![];
+    let mut p = full_path.as_path();
+    while !p.exists() {
+        missing.push(p);
+        p = match p.parent() {
+            Some(p) => p,
+            None => break,
+        };
+    }
+
+    for folder in missing.iter().rev() {
+        let (Some(base), Some(name)) = (folder.parent(), folder.file_name()) else {
+            continue;
+        };
+        ui::create_folder(
+            base.to_path_buf(),
+            name.to_string_lossy().to_string(),
+            true,
+            ctx,
+        )?;
+    }
+
+    Ok(full_path)
+}
@@ -1,14 +1,15 @@
-use std::fs::{self, DirEntry};
-use std::path::PathBuf;
-use std::time::UNIX_EPOCH;
-
-use chrono::{self, NaiveDateTime};
-use clap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cabinet::filter::{DateFilter, Filter};
+use cabinet::sort::date::{modified_timestamp, parse_date, GroupBy};
+use cabinet::util;
+use cabinet::util::walk::{remove_nested, Entry};
+use cabinet::Plan;
 use clap::{Arg, ArgMatches, Command};
-use regex::Regex;
 
-use crate::util;
-use crate::util::path::{get_current_path, get_path};
+use crate::context::Context;
+use crate::ui::{self, get_current_path, get_path};
 
 pub fn cli() -> Command {
     Command::new("date")
@@ -18,13 +19,20 @@ pub fn cli() -> Command {
                 .short('B')
                 .long("before")
                 .value_name("date")
-                .help("Get files from before the specified date. Date format is YYYY-MM-DD")
+                .help("Get files from before the specified date, e.g. 2024-03-15, \"2024-03-15 14:30\", 7d, \"3 weeks ago\" or yesterday")
                 .action(clap::ArgAction::Set),
             Arg::new("after")
                 .short('A')
                 .long("after")
                 .value_name("date")
-                .help("Get files from after the specified date. Date format is YYYY-MM-DD")
+                .help("Get files from after the specified date, e.g. 2024-03-15, \"2024-03-15 14:30\", 7d, \"3 weeks ago\" or yesterday")
+                .action(clap::ArgAction::Set),
+            Arg::new("group-by")
+                .short('G')
+                .long("group-by")
+                .value_name("period")
+                .help("Sort files into a folder for the year, quarter, month, week or day they were modified, e.g. 2024/2024-03")
+                .value_parser(["year", "quarter", "month", "week", "day"])
                 .action(clap::ArgAction::Set),
             Arg::new("template")
                 .short('t')
@@ -42,124 +50,71 @@ pub fn cli() -> Command {
         .subcommand_value_name("PATH")
 }
 
-pub fn exec(args: &ArgMatches) {
+pub fn exec(args: &ArgMatches, ctx: &mut Context) {
     let mut path: Option<PathBuf> = None;
     let use_template = args.get_flag("template");
 
     if let Some(p) = args.get_one::<String>("path") {
-        path = get_path(p, use_template);
+        path = get_path(p, use_template, &ctx.config);
     }
     if path.is_none() {
         println!("ERROR: The path is invalid");
         return;
     }
 
-    let mut date_after: Option<String> = None;
-    let mut date_before: Option<String> = None;
+    let date_after = args.get_one::<String>("after");
+    let date_before = args.get_one::<String>("before");
 
-    // Date pattern for YYYY-MM-DD
-    let re = Regex::new(r"^\d{4}-\d{2}-\d{2}$").unwrap();
-
-    if let Some(after) = args.get_one::<String>("after") {
-        if re.is_match(after) {
-            date_after = Some(after.to_string());
-        }
-    }
-    if let Some(before) = args.get_one::<String>("before") {
-        if re.is_match(before) {
-            date_before = Some(before.to_string());
-        }
-    }
+    let group_by = match args.get_one::<String>("group-by").map(String::as_str) {
+        Some("year") => Some(GroupBy::Year),
+        Some("quarter") => Some(GroupBy::Quarter),
+        Some("month") => Some(GroupBy::Month),
+        Some("week") => Some(GroupBy::Week),
+        Some("day") => Some(GroupBy::Day),
+        _ => None,
+    };
 
-    if date_before.is_none() && date_after.is_none() {
-        println!("ERROR: A before or after date must be provided");
+    if date_before.is_none() && date_after.is_none() && group_by.is_none() {
+        println!("ERROR: A before or after date (or both), or --group-by, must be provided");
         return;
     }
 
-    let re = Regex::new(r"(\d{4})-(\d{2})-(\d{2})").unwrap();
-
-    let mut has_before = false;
-    let mut has_after = false;
-    let mut before: i64 = 0;
-    let mut after: i64 = 0;
-
-    if let Some(date_before_val) = &date_before {
-        has_before = true;
-        let d = &date_before_val[..];
-        let cap = re.captures(d).unwrap();
-        //let text = "2012-03-14, 2013-01-01 and 2014-07-05";
-        //for cap in re.captures_iter(text) {
-        //    println!("Day: {} Month: {} Year: {}", &cap[3], &cap[2], &cap[1]);
-        //}
-
-        let date_time = chrono::NaiveDate::from_ymd_opt(
-            cap[1].parse::<i32>().unwrap(),
-            cap[2].parse::<u32>().unwrap(),
-            cap[3].parse::<u32>().unwrap(),
-        );
-        let naive_date_time: NaiveDateTime = match date_time {
-            Some(d) => d.and_hms_opt(0, 0, 0).unwrap(),
-            None => {
-                println!("ERROR: Invalid date conversion");
-                return;
-            }
-        };
-        before = naive_date_time.and_utc().timestamp();
-    }
-    if let Some(date_after_val) = &date_after {
-        has_after = true;
-        let d = &date_after_val[..];
-        let cap = re.captures(d).unwrap();
-        let date_time = chrono::NaiveDate::from_ymd_opt(
-            cap[1].parse::<i32>().unwrap(),
-            cap[2].parse::<u32>().unwrap(),
-            cap[3].parse::<u32>().unwrap(),
-        );
-        let naive_date_time: NaiveDateTime = match date_time {
-            Some(d) => d.and_hms_opt(0, 0, 0).unwrap(),
-            None => {
-                println!("ERROR: Invalid date conversion");
-                return;
-            }
-        };
-        after = naive_date_time.and_utc().timestamp();
-    }
+    let date = |d: Option<&String>, option: &str| -> Result<Option<i64>, String> {
+        d.map(|d| {
+            parse_date(d).map_err(|error| format!("Invalid date '{d}' for --{option}. {error}"))
+        })
+        .transpose()
+    };
+    let filter = match (date(date_before, "before"), date(date_after, "after")) {
+        (Ok(before), Ok(after)) => DateFilter { before, after },
+        (Err(error), _) | (_, Err(error)) => {
+            println!("ERROR: {error}");
+            return;
+        }
+    };
 
-    let dir = fs::read_dir(path.as_ref().unwrap()).unwrap();
+    // Files that have already been grouped are not sorted again
+    let skip = match group_by {
+        Some(_) => year_folders(path.as_ref().unwrap()),
+        None => vec![],
+    };
+    let dir = ui::walk(path.as_ref().unwrap(), &skip, ctx);
     let parent = get_current_path(path);
 
-    let mut files: Vec<DirEntry> = vec![];
-    for item in dir {
-        let item = item.unwrap();
-        let md = item.metadata().unwrap();
-        //let md = fs::metadata(item)?; // Alternative method
-
-        if md.is_file() {
-            //if let Ok(time) = md.modified() {
-            //    println!("{:?}", time);
-            //} else {
-            //    println!("Not supported on this platform");
-            //}
-
-            let time = md.modified().unwrap().duration_since(UNIX_EPOCH).unwrap(); // TODO: error handling
-            let dur = chrono::Duration::from_std(time).unwrap();
-            let file_date = dur.num_seconds();
-
-            if (!has_after || file_date >= after)
-                && (has_after || has_before)
-                && (file_date <= before || !has_before)
-            {
-                files.push(item);
-            }
-        }
-    }
+    let files: Vec<Entry> = dir.into_iter().filter(|e| filter.matches(e)).collect();
 
+    let files = remove_nested(files);
     if files.is_empty() {
         println!("There are no files to sort that match the given parameters");
         return;
     }
     println!("Found {} files that are able to be sorted", &files.len());
 
+    if let Some(group_by) = group_by {
+        group(args, group_by, parent, &files, ctx);
+        return;
+    }
+
     let mut folder = util::set_folder_name("Sorted_by_Date".to_string());
 
     if let Some(out_name) = args.get_one::<String>("output") {
@@ -169,10 +124,56 @@ pub fn exec(args: &ArgMatches) {
     }
 
     let full_path = parent.clone();
-    let full_path = match util::create_folder(full_path, folder, false) {
+    let full_path = match ui::create_folder(full_path, folder, false, ctx) {
         Ok(f) => f,
         Err(_) => return,
     };
 
-    util::sort_files(&full_path, &files);
+    ui::sort_files(&full_path, &files, ctx);
+}
+
+/// Sort each file into the folder for its date. The folders are created in the folder being
+/// sorted, or in the output folder if one is given.
+fn group(
+    args: &ArgMatches,
+    group_by: GroupBy,
+    parent: PathBuf,
+    files: &[Entry],
+    ctx: &mut Context,
+) {
+    let root = match ui::output_root(args, parent, ctx) {
+        Some(r) => r,
+        None => return,
+    };
+
+    let plan = Plan::new(files, |e| {
+        Some(root.join(group_by.folder(modified_timestamp(e.metadata())?)?))
+    });
+    if plan.is_empty() {
+        println!("There are no files to sort that match the given parameters");
+        return;
+    }
+    println!(
+        "{} files will be sorted into {} folders",
+        plan.len(),
+        plan.folders.len()
+    );
+
+    ui::run_plan(&plan, plan.len(), ctx);
+}
+
+/// The folders for each year created by `--group-by`, such as `2024`
+fn year_folders(path: &Path) -> Vec<PathBuf> {
+    let Ok(dir) = fs::read_dir(path) else {
+        return vec![];
+    };
+    dir.filter_map(|item| item.ok())
+        .filter(|item| item.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|item| {
+            let name = item.file_name();
+            let name = name.to_string_lossy();
+            name.len() == 4 && name.chars().all(|c| c.is_ascii_digit())
+        })
+        .map(|item| item.path())
+        .collect()
 }
@@ -1,14 +1,13 @@
-use std::ffi::OsStr;
-use std::fs;
-use std::fs::DirEntry;
-use std::io::{stdout, Write};
-use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::path::PathBuf;
 
+use cabinet::filter::{Any, Filter, TypeFilter};
+use cabinet::sort::file_type::{extension, mismatch, By, Categories, Detect};
+use cabinet::util::walk::{Entries, Entry};
+use cabinet::Plan;
 use clap::{Arg, ArgMatches, Command};
 
-use crate::util;
-use crate::util::path::get_path;
+use crate::context::Context;
+use crate::ui::{self, get_path};
 
 pub fn cli() -> Command {
     Command::new("type")
@@ -18,8 +17,44 @@ pub fn cli() -> Command {
                 .short('T')
                 .long("type")
                 .value_name("file-type")
-                .help("Sort files according to the specific file type")
+                .value_delimiter(',')
+                .help("Sort files of the given file types (e.g. jpg,png,webp) into a folder for each type, or all into the --output folder")
+                .action(clap::ArgAction::Append),
+            Arg::new("exclude-type")
+                .long("exclude-type")
+                .value_name("file-type")
+                .value_delimiter(',')
+                .help("Leave files of the given file types (e.g. iso,dmg) where they are")
+                .conflicts_with("type")
+                .action(clap::ArgAction::Append),
+            Arg::new("by")
+                .long("by")
+                .value_name("folders")
+                .help("Sort files into a folder for each file type (e.g. jpg), each category (e.g. Images), or both (e.g. Images/jpg)")
+                .value_parser(["extension", "category", "category/extension"])
+                .default_value("extension")
+                .action(clap::ArgAction::Set),
+            Arg::new("detect")
+                .long("detect")
+                .value_name("source")
+                .help("Take the file type from the extension, from the contents of the file, or from the extension unless the contents show it is wrong")
+                .value_parser(["extension", "content", "both"])
+                .default_value("extension")
+                .action(clap::ArgAction::Set),
+            Arg::new("case-sensitive")
+                .long("case-sensitive")
+                .help("Treat extensions that only differ in case as different file types (e.g. jpg and JPG)")
+                .action(clap::ArgAction::SetTrue),
+            Arg::new("no-extension")
+                .long("no-extension")
+                .value_name("folder")
+                .help("The folder for files without a file type")
+                .default_value("no-extension")
                 .action(clap::ArgAction::Set),
+            Arg::new("skip-no-extension")
+                .long("skip-no-extension")
+                .help("Leave files without a file type where they are")
+                .action(clap::ArgAction::SetTrue),
             Arg::new("template")
                 .short('t')
                 .long("template")
@@ -36,83 +71,204 @@ pub fn cli() -> Command {
         .subcommand_value_name("PATH")
 }
 
-pub fn exec(args: &ArgMatches) {
+pub fn exec(args: &ArgMatches, ctx: &mut Context) {
     let mut path: Option<PathBuf> = None;
     let use_template = args.get_flag("template");
 
     if let Some(p) = args.get_one::<String>("path") {
-        path = get_path(p, use_template);
+        path = get_path(p, use_template, &ctx.config);
     }
     if path.is_none() {
         println!("ERROR: The path is invalid");
         return;
     }
 
-    if args.get_one::<String>("output").is_some() {
-        println!("NOTE: Setting a custom output folder is currently not possible when sorting by file type");
+    let types: Vec<&String> = match args.get_many::<String>("type") {
+        Some(types) => types.filter(|t| !t.is_empty()).collect(),
+        None => vec![],
+    };
+    let output = args.get_one::<String>("output").filter(|o| !o.is_empty());
+    if output.is_some() && types.is_empty() {
+        println!("NOTE: Setting a custom output folder is only possible when sorting select file types with --type");
+    }
+    if ctx.walk.entries != Entries::Files {
+        println!("NOTE: Folders do not have a file type, so only files will be sorted");
     }
 
-    let dir = fs::read_dir(path.as_ref().unwrap()).unwrap();
+    // Files that are already in the folder for their file type are not sorted again
+    let mut skip: Vec<PathBuf> = vec![];
+    match output {
+        Some(output) if !types.is_empty() => skip.push(path.as_ref().unwrap().join(output)),
+        _ => skip.extend(types.iter().map(|t| path.as_ref().unwrap().join(t))),
+    }
+
+    let detect = match args.get_one::<String>("detect").map(String::as_str) {
+        Some("content") => Detect::Content,
+        Some("both") => Detect::Both,
+        _ => Detect::Extension,
+    };
+    let case_sensitive = args.get_flag("case-sensitive");
+
+    let dir = ui::walk(path.as_ref().unwrap(), &skip, ctx);
     let paths_parent = path.as_ref().unwrap().display().to_string(); // As a String
     let parent = path.unwrap(); // PathBuf
     println!("CURRENT PATH: {}", &paths_parent);
 
-    let mut files: Vec<DirEntry> = vec![];
+    if detect != Detect::Extension {
+        warn_mismatches(&dir);
+    }
+
+    let mut files: Vec<Entry> = vec![];
     let mut file_types: Vec<String> = vec![];
 
-    if let Some(ftype) = args.get_one::<String>("type") {
-        let folder = ftype.to_string();
+    let type_filter = |t: &str| {
+        TypeFilter::new(t)
+            .detect(detect)
+            .case_sensitive(case_sensitive)
+    };
 
-        let full_path = parent.clone();
-        let full_path = match util::create_folder(full_path, folder, true) {
-            Ok(f) => f,
-            Err(_) => return,
+    if !types.is_empty() {
+        let filters: Vec<(&String, TypeFilter)> =
+            types.iter().map(|t| (*t, type_filter(t))).collect();
+        let type_of = |item: &Entry| {
+            filters
+                .iter()
+                .find(|(_, filter)| filter.matches(item))
+                .map(|(t, _)| *t)
         };
+        files = dir.into_iter().filter(|e| type_of(e).is_some()).collect();
+        if files.is_empty() {
+            println!("There are no files to sort");
+            return;
+        }
 
-        for item in dir {
-            let item = item.unwrap();
-            let md = item.metadata().unwrap();
-
-            if md.is_file() {
-                let filename = &item.file_name();
-                let extension = Path::new(filename).extension().and_then(OsStr::to_str);
-                let ext: String = match extension {
-                    Some(f) => f.to_string(),
-                    None => continue,
-                };
-                if ext == *ftype {
-                    files.push(item);
-                }
-            } else {
+        // With an output folder every file goes in it, otherwise each file type has its own
+        if let Some(output) = output {
+            let full_path = match ui::create_folder(parent, output.to_string(), false, ctx) {
+                Ok(f) => f,
+                Err(_) => return,
+            };
+            ui::sort_files(&full_path, &files, ctx);
+            return;
+        }
+
+        let mut plan = Plan::default();
+        for ftype in &types {
+            let of_type: Vec<&Entry> = files
+                .iter()
+                .filter(|e| type_of(e) == Some(*ftype))
+                .collect();
+            if of_type.is_empty() {
                 continue;
             }
+            let full_path = match ui::create_folder(parent.clone(), ftype.to_string(), true, ctx) {
+                Ok(f) => f,
+                Err(_) => return,
+            };
+            for item in of_type {
+                plan.push(item, &full_path);
+            }
         }
+        ui::run_plan(&plan, files.len(), ctx);
+        return;
+    }
 
-        util::sort_files(&full_path, &files);
+    let by = match args.get_one::<String>("by").map(String::as_str) {
+        Some("category") => By::Category,
+        Some("category/extension") => By::CategoryExtension,
+        _ => By::Extension,
+    };
+    let categories = Categories::new(&ctx.config.categories);
 
+    // Files without an extension go in a folder of their own, unless they are skipped. Files with
+    // an extension whose contents are not recognised (with `--detect content`) are left where
+    // they are.
+    let skip_no_extension = args.get_flag("skip-no-extension");
+    let no_extension = args
+        .get_one::<String>("no-extension")
+        .map(String::as_str)
+        .unwrap_or("no-extension");
+    if no_extension.is_empty()
+        || no_extension.contains(['/', '\\'])
+        || no_extension == "."
+        || no_extension == ".."
+    {
+        println!("ERROR: '{no_extension}' cannot be used as a folder name");
         return;
     }
+    let has_extension = |item: &Entry| extension(&item.file_name()).is_some();
+    let folder_for = |item: &Entry| match detect.file_type(item, case_sensitive) {
+        Some(f) => Some(parent.join(by.folder(&f, &categories))),
+        None if skip_no_extension || has_extension(item) => None,
+        // Not a file type, so it is not put in a category
+        None => Some(parent.join(no_extension)),
+    };
+
+    // The folders to sort files into, in the order they are first used
+    let mut folders: Vec<PathBuf> = vec![];
+    let mut skipped = 0;
+    let mut unrecognised = 0;
+
+    // Files of the excluded file types are left where they are
+    let mut excluded = Any::new();
+    for ftype in args
+        .get_many::<String>("exclude-type")
+        .into_iter()
+        .flatten()
+    {
+        excluded.push(type_filter(ftype));
+    }
+    let mut excluded_count = 0;
 
     for item in dir {
-        let item = item.unwrap();
-        let md = item.metadata().unwrap();
-        if md.is_file() {
-            let filename = &item.file_name();
+        if !item.is_dir() {
+            if excluded.matches(&item) {
+                excluded_count += 1;
+                continue;
+            }
+            let f_type = detect.file_type(&item, case_sensitive);
+            if f_type.is_none() && has_extension(&item) {
+                unrecognised += 1;
+                continue;
+            }
+            if f_type.is_none() && skip_no_extension {
+                skipped += 1;
+                continue;
+            }
+            let folder = folder_for(&item);
+            if folder
+                .as_ref()
+                .is_some_and(|f| item.path().parent() == Some(f))
+            {
+                continue;
+            }
             files.push(item);
 
-            let f_type = Path::new(filename).extension().and_then(OsStr::to_str);
             if let Some(f) = f_type {
-                let ff = f.to_string();
-                if !file_types.contains(&ff) {
-                    file_types.push(ff);
+                if !file_types.contains(&f) {
+                    file_types.push(f);
                 }
             };
+            if let Some(f) = folder {
+                if !folders.contains(&f) {
+                    folders.push(f);
+                }
+            }
         } else {
             // Ignore directories (for now)
             continue;
         }
     }
 
+    if excluded_count > 0 {
+        println!("NOTE: {excluded_count} files of an excluded file type were skipped");
+    }
+    if skipped > 0 {
+        println!("NOTE: {skipped} files without a file type were skipped");
+    }
+    if unrecognised > 0 {
+        println!("NOTE: {unrecognised} files were skipped as their contents are not recognised. Use --detect both to sort them by their extension");
+    }
     if files.is_empty() {
         println!("There are no files to sort");
         return;
@@ -123,49 +279,39 @@ pub fn exec(args: &ArgMatches) {
         &file_types.len()
     );
 
-    // Create file type paths
-    for file_type in &file_types {
-        let full_path = parent.clone();
-        _ = util::create_folder(full_path, file_type.to_string(), true);
+    // Create the folders, along with the category folders they are in
+    let mut created: Vec<PathBuf> = vec![];
+    for folder in &folders {
+        let Ok(relative) = folder.strip_prefix(&parent) else {
+            continue;
+        };
+        let mut base = parent.clone();
+        for name in relative {
+            let full_path = base.join(name);
+            if !created.contains(&full_path) {
+                _ = ui::create_folder(base.clone(), name.to_string_lossy().to_string(), true, ctx);
+                created.push(full_path.clone());
+            }
+            base = full_path;
+        }
     }
 
-    // TODO: Check if output is specified and warn user that it will not be used for
-    // sorting all files by their file type(s)
-
-    let mut files_sorted: f64 = 0.0;
-    let start = Instant::now();
     // TODO: maybe have progress bar
-    let mut stdout = stdout();
-    for (idx, file) in files.iter().enumerate() {
-        let done = idx as f64 / files.len() as f64;
-
-        let fname = &file.file_name();
-        let ext = Path::new(fname).extension().and_then(OsStr::to_str);
-        let ff: String = match ext {
-            Some(f) => f.to_string(),
-            None => continue,
-        };
-
-        // get original directory and navigate to file type directories
-        let full_path = parent.clone().join(ff);
-
-        let f = fs::rename(file.path(), full_path.join(file.file_name()));
-        match f {
-            Ok(_) => files_sorted += 1.0,
-            Err(error) => println!("There was a problem opening the file:\n{:?}", error),
-        };
+    let plan = Plan::new(&files, folder_for);
+    ui::run_plan(&plan, files.len(), ctx);
+}
 
-        print!("\rProcessing {:.1}%", done * 100.0);
-        stdout.flush().unwrap();
+/// Warn about files whose extension does not match their contents, such as a JPEG image named
+/// `photo.png`
+fn warn_mismatches(dir: &[Entry]) {
+    for item in dir {
+        if let Some(content) = mismatch(item) {
+            println!(
+                "WARNING: {} has the wrong extension, as its contents are {} ({})",
+                item.path().display(),
+                content.file_type,
+                content.mime
+            );
+        }
     }
-
-    let duration = start.elapsed();
-    stdout.flush().unwrap();
-    print!("\rProcessed 100%   \n");
-    println!("Time taken: {:?}", duration);
-    println!(
-        "Sorted {}/{} files into folders",
-        &files_sorted,
-        &files.len()
-    );
 }
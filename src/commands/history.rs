@@ -0,0 +1,172 @@
+use cabinet::sort::date::parse_date;
+use cabinet::util::journal::{self, Entry, Run};
+use clap::{Arg, ArgMatches, Command};
+
+use crate::commands::undo::find_run;
+
+pub fn cli() -> Command {
+    Command::new("history")
+        .about("List previous runs that can be reverted with 'cab undo'")
+        .args([
+            Arg::new("run")
+                .action(clap::ArgAction::Set)
+                .value_name("RUN_ID")
+                .help("Show every change made by the given run"),
+            Arg::new("limit")
+                .short('n')
+                .long("limit")
+                .value_name("count")
+                .help("Maximum number of runs to list")
+                .default_value("10")
+                .value_parser(clap::value_parser!(usize))
+                .action(clap::ArgAction::Set),
+            Arg::new("prune")
+                .long("prune")
+                .value_name("date")
+                .help("Remove the runs made before the given date (e.g. 30d or 2024-01-01), along with the files they replaced. These runs can no longer be undone")
+                .conflicts_with("run")
+                .action(clap::ArgAction::Set),
+        ])
+}
+
+pub fn exec(args: &ArgMatches) {
+    let history = match journal::load_all() {
+        Ok(h) => h,
+        Err(error) => {
+            println!("ERROR: Unable to read the run history:\n{:?}", error);
+            return;
+        }
+    };
+    for warning in &history.warnings {
+        println!("WARNING: {warning}");
+    }
+    let runs = history.runs;
+
+    if let Some(date) = args.get_one::<String>("prune") {
+        prune(runs, date, args.get_flag("dry-run"));
+        return;
+    }
+
+    if let Some(id) = args.get_one::<String>("run") {
+        match find_run(runs, Some(id)) {
+            Some(run) => print_run(&run),
+            None => println!("ERROR: There is no run with the id '{id}'"),
+        }
+        return;
+    }
+
+    if runs.is_empty() {
+        println!("There are no runs in the history");
+        return;
+    }
+
+    let limit = *args.get_one::<usize>("limit").unwrap();
+    println!(
+        "{:<20} {:<20} {:>6}  {:<7} COMMAND",
+        "RUN ID", "DATE", "FILES", "STATUS"
+    );
+    for run in runs.iter().take(limit) {
+        println!(
+            "{:<20} {:<20} {:>6}  {:<7} {}",
+            &run.id,
+            format_time(run),
+            run.moved_count(),
+            if run.undone.is_some() {
+                "undone"
+            } else {
+                "done"
+            },
+            run.command_line()
+        );
+    }
+}
+
+/// Remove the runs made before `date`
+fn prune(runs: Vec<Run>, date: &str, dry_run: bool) {
+    let before = match parse_date(date) {
+        Ok(t) => t,
+        Err(error) => {
+            println!("ERROR: Invalid date '{date}' for --prune. {error}");
+            return;
+        }
+    };
+    let old: Vec<Run> = runs
+        .into_iter()
+        .filter(|r| r.time.is_some_and(|t| t.timestamp() < before))
+        .collect();
+    if old.is_empty() {
+        println!("There are no runs made before {date}");
+        return;
+    }
+
+    let total = old.len();
+    let mut removed = 0;
+    for run in old {
+        if dry_run {
+            println!("Run {} would be removed: {}", &run.id, run.command_line());
+            removed += 1;
+            continue;
+        }
+        let id = run.id.clone();
+        match run.remove() {
+            Ok(_) => removed += 1,
+            Err(error) => println!("ERROR: Unable to remove run '{id}':\n{:?}", error),
+        }
+    }
+
+    if dry_run {
+        println!("Dry run: {removed}/{total} runs would be removed. Nothing was changed");
+    } else {
+        println!("Removed {removed}/{total} runs");
+    }
+}
+
+fn print_run(run: &Run) {
+    println!("RUN ID:  {}", &run.id);
+    println!("DATE:    {}", format_time(run));
+    println!("COMMAND: {}", run.command_line());
+    println!("CWD:     {}", run.cwd.display());
+    if let Some(t) = &run.undone {
+        println!("UNDONE:  {t}");
+    }
+    println!();
+
+    for entry in &run.entries {
+        match entry {
+            Entry::Mkdir { path } => println!("mkdir  {}", path.display()),
+            Entry::Move {
+                source,
+                destination,
+                ..
+            } => println!("move   {} -> {}", source.display(), destination.display()),
+            Entry::Create {
+                action,
+                source,
+                destination,
+                ..
+            } => println!(
+                "{:<6} {} -> {}",
+                action,
+                source.display(),
+                destination.display()
+            ),
+            Entry::Remove {
+                path, duplicate_of, ..
+            } => println!(
+                "remove {} (duplicate of {})",
+                path.display(),
+                duplicate_of.display()
+            ),
+            Entry::Replace { path, backup } => {
+                println!("replace {} (kept as {})", path.display(), backup.display())
+            }
+        }
+    }
+}
+
+fn format_time(run: &Run) -> String {
+    match run.time {
+        Some(t) => t.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => "unknown".to_string(),
+    }
+}
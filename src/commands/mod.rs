@@ -1,17 +1,23 @@
 use clap::Command;
 
+pub mod apply;
 pub mod date;
 pub mod file_type;
+pub mod history;
 pub mod multisort;
 pub mod name;
 pub mod size;
+pub mod undo;
 
 pub fn builtin() -> Vec<Command> {
     vec![
+        apply::cli(),
         date::cli(),
         multisort::cli(),
         name::cli(),
         size::cli(),
         file_type::cli(),
+        history::cli(),
+        undo::cli(),
     ]
 }
@@ -1,15 +1,18 @@
-use std::ffi::OsStr;
-use std::fs::{self, DirEntry};
-use std::path::{Path, PathBuf};
-use std::rc::Rc;
-use std::time::UNIX_EPOCH;
-
-use chrono::NaiveDateTime;
+use std::path::PathBuf;
+
+use cabinet::filter::{
+    expr, All, Any, DateFilter, Filter, NameFilter, Not, Pattern, SizeFilter, TypeFilter,
+};
+use cabinet::sort::date::parse_date;
+use cabinet::sort::file_type::Detect;
+use cabinet::sort::size::{parse_size, KB};
+use cabinet::util;
+use cabinet::util::walk::{remove_nested, Entry};
 use clap::{Arg, ArgMatches, Command};
-use regex::Regex;
 
-use crate::util;
-use crate::util::path::get_path;
+use crate::commands::name::pattern_syntax;
+use crate::context::Context;
+use crate::ui::{self, get_path};
 
 pub fn cli() -> Command {
     Command::new("multisort")
@@ -20,49 +23,81 @@ pub fn cli() -> Command {
                 .short('B')
                 .long("before")
                 .value_name("date")
-                .help("Get files from before the specified date. Date format is YYYY-MM-DD")
-                .action(clap::ArgAction::Set),
+                .help("Get files from before the specified date, e.g. 2024-03-15, \"2024-03-15 14:30\", 7d, \"3 weeks ago\" or yesterday")
+                .action(clap::ArgAction::Append),
             Arg::new("after")
                 .short('A')
                 .long("after")
                 .value_name("date")
-                .help("Get files from after the specified date. Date format is YYYY-MM-DD")
-                .action(clap::ArgAction::Set),
+                .help("Get files from after the specified date, e.g. 2024-03-15, \"2024-03-15 14:30\", 7d, \"3 weeks ago\" or yesterday")
+                .action(clap::ArgAction::Append),
             Arg::new("includes")
                 .short('I')
                 .long("includes")
                 .value_name("match")
                 .help("File name includes...")
-                .action(clap::ArgAction::Set),
+                .action(clap::ArgAction::Append),
             Arg::new("excludes")
                 .short('E')
                 .long("excludes")
                 .value_name("match")
                 .help("File name excludes...")
-                .action(clap::ArgAction::Set),
+                .action(clap::ArgAction::Append),
             Arg::new("min")
                 .short('m')
                 .long("min")
                 .value_name("size")
-                .help("Get files that are GREATER THAN the specified size (in KB)")
-                .action(clap::ArgAction::Set),
+                .help("Get files that are GREATER THAN the specified size, e.g. 500KB or 1.5GiB (in KB without a unit)")
+                .action(clap::ArgAction::Append),
             Arg::new("max")
                 .short('M')
                 .long("max")
                 .value_name("size")
-                .help("Get files that are LESS THAN the specified size (in KB)")
-                .action(clap::ArgAction::Set),
+                .help("Get files that are LESS THAN the specified size, e.g. 500KB or 1.5GiB (in KB without a unit)")
+                .action(clap::ArgAction::Append),
             Arg::new("type")
                 .short('T')
                 .long("type")
                 .value_name("file-type")
-                .help("Sort files according to the specific file type")
+                .value_delimiter(',')
+                .help("Sort files of the given file types, e.g. jpg,png,webp")
+                .action(clap::ArgAction::Append),
+            Arg::new("detect")
+                .long("detect")
+                .value_name("source")
+                .help("Take the file type for --type from the extension, from the contents of the file, or from the extension unless the contents show it is wrong")
+                .value_parser(["extension", "content", "both"])
+                .default_value("extension")
                 .action(clap::ArgAction::Set),
+            Arg::new("case-sensitive")
+                .long("case-sensitive")
+                .help("Only match file types for --type with the same case (e.g. jpg but not JPG)")
+                .action(clap::ArgAction::SetTrue),
+            Arg::new("where")
+                .short('W')
+                .long("where")
+                .value_name("expression")
+                .help("Get files that match an expression, e.g. \"ext in (jpg, png) and size > 2MB\"")
+                .action(clap::ArgAction::Append),
+            Arg::new("any")
+                .long("any")
+                .help("Sort files that match any of the given criteria, instead of all of them")
+                .action(clap::ArgAction::SetTrue),
             Arg::new("regex")
                 .short('R')
                 .long("regex")
                 .help("Use regular expressions (regex) for pattern matching of file names")
                 .action(clap::ArgAction::SetTrue),
+            Arg::new("glob")
+                .long("glob")
+                .help("Use glob patterns (e.g. 'IMG_*.jpg') that match the whole file name")
+                .conflicts_with("regex")
+                .action(clap::ArgAction::SetTrue),
+            Arg::new("ignore-case")
+                .short('i')
+                .long("ignore-case")
+                .help("Match file name patterns regardless of case")
+                .action(clap::ArgAction::SetTrue),
             Arg::new("template")
                 .short('t')
                 .long("template")
@@ -79,331 +114,178 @@ pub fn cli() -> Command {
         .subcommand_value_name("PATH")
 }
 
-pub fn exec(args: &ArgMatches) {
+pub fn exec(args: &ArgMatches, ctx: &mut Context) {
     let mut path: Option<PathBuf> = None;
     let use_template = args.get_flag("template");
 
     if let Some(p) = args.get_one::<String>("path") {
-        path = get_path(p, use_template);
+        path = get_path(p, use_template, &ctx.config);
     }
     if path.is_none() {
         println!("ERROR: The path is invalid");
         return;
     }
 
-    // Date pattern for YYYY-MM-DD
-    let re = Regex::new(r"^\d{4}-\d{2}-\d{2}$").unwrap();
-    let mut date_after: Option<String> = None;
-    let mut date_before: Option<String> = None;
-    if let Some(after) = args.get_one::<String>("after") {
-        if re.is_match(after) {
-            date_after = Some(after.to_string());
-        }
-    }
-    if let Some(before) = args.get_one::<String>("before") {
-        if re.is_match(before) {
-            date_before = Some(before.to_string());
-        }
-    }
-
-    let mut include_pattern: Option<String> = None;
-    let mut exclude_pattern: Option<String> = None;
-    let use_regex = args.get_flag("regex");
-    if let Some(incl) = args.get_one::<String>("includes") {
-        include_pattern = Some(incl.to_string());
-    }
-    if let Some(excl) = args.get_one::<String>("excludes") {
-        exclude_pattern = Some(excl.to_string());
-    }
-
-    let mut size_min: Option<String> = None;
-    let mut size_max: Option<String> = None;
-    if let Some(min) = args.get_one::<String>("min") {
-        size_min = Some(min.to_string());
-    }
-    if let Some(max) = args.get_one::<String>("max") {
-        size_max = Some(max.to_string());
-    }
-
-    let mut file_type: Option<String> = None;
-    if let Some(ftype) = args.get_one::<String>("type") {
-        file_type = Some(ftype.to_string());
-    }
-
-    // No options were provided
-    if size_min.is_none()
-        && size_max.is_none()
-        && include_pattern.is_none()
-        && exclude_pattern.is_none()
-        && date_before.is_none()
-        && date_after.is_none()
-        && file_type.is_none()
-    {
-        println!("ERROR: At least one option must be provided");
-        return;
-    }
-
-    let dir = fs::read_dir(path.as_ref().unwrap()).unwrap();
-    let paths_parent = path.as_ref().unwrap().display().to_string();
-    let parent = path.unwrap();
-    println!("CURRENT PATH: {}", &paths_parent);
-
-    let mut files: Vec<Rc<DirEntry>> = vec![];
-    let mut sort_files = false;
-
-    for item in dir {
-        let item = item.unwrap();
-        //files.push(item.into());
-        files.push(Rc::new(item));
-    }
-
-    // Sort by name
-
-    let mut new_files: Vec<Rc<DirEntry>> = vec![];
-
-    let mut has_include = false;
-    let mut has_exclude = false;
-
-    if use_regex {
-        let mut include: Regex = Regex::new("").unwrap();
-        let mut exclude: Regex = Regex::new("").unwrap();
-
-        if let Some(p) = include_pattern {
-            has_include = true;
-            include = Regex::new(p.as_str()).unwrap();
-        }
-        if let Some(p) = exclude_pattern {
-            has_exclude = true;
-            exclude = Regex::new(p.as_str()).unwrap();
-        }
-
-        for item in &files {
-            let md = item.metadata().unwrap();
-
-            let filename = &item.file_name();
-            let f = OsStr::to_str(filename).unwrap();
-
-            if md.is_file() {
-                if has_include && has_exclude {
-                    match include.captures(f) {
-                        Some(_) => {}
-                        None => continue,
-                    };
-                    match exclude.captures(f) {
-                        Some(_) => {}
-                        None => continue,
-                    };
-                    new_files.push(Rc::clone(item));
-                } else if has_include && !has_exclude {
-                    match include.captures(f) {
-                        Some(_) => {}
-                        None => continue,
-                    };
-                    new_files.push(Rc::clone(item));
-                } else if has_exclude && !has_include {
-                    match exclude.captures(f) {
-                        Some(_) => {}
-                        None => continue,
-                    };
-                    new_files.push(Rc::clone(item));
-                }
-            }
-        }
-
-        if !&new_files.is_empty() {
-            files = new_files;
-            sort_files = true;
-        } else if new_files.is_empty() && (has_include || has_exclude) {
-            files = vec![];
-        }
-    } else {
-        let mut include = String::new();
-        let mut exclude = String::new();
+    let values = |id: &str| -> Vec<&String> {
+        args.get_many::<String>(id)
+            .map(|v| v.collect())
+            .unwrap_or_default()
+    };
 
-        if let Some(p) = include_pattern {
-            has_include = true;
-            include = p;
-        }
-        if let Some(p) = exclude_pattern {
-            has_exclude = true;
-            exclude = p;
+    // Every option can be given more than once. The values of an option are combined with OR,
+    // since a file can only have one file type, one size and so on. The options themselves
+    // must all match, unless `--any` is given.
+    let mut criteria: Vec<Box<dyn Filter>> = vec![];
+    let mut add = |filters: Vec<Box<dyn Filter>>| {
+        if !filters.is_empty() {
+            criteria.push(Box::new(Any(filters)));
         }
+    };
 
-        for item in &files {
-            let md = item.metadata().unwrap();
-
-            let filename = &item.file_name();
-            let f = OsStr::to_str(filename).unwrap();
-
-            if md.is_file()
-                && ((has_include && has_exclude && f.contains(&include) && !f.contains(&exclude))
-                    || (has_include && !has_exclude && f.contains(&include))
-                    || (has_exclude && !has_include && !f.contains(&exclude)))
-            {
-                new_files.push(Rc::clone(item));
-            }
+    let dates = |option: &str| -> Result<Vec<i64>, String> {
+        values(option)
+            .into_iter()
+            .map(|d| {
+                parse_date(d).map_err(|error| format!("Invalid date '{d}' for --{option}. {error}"))
+            })
+            .collect()
+    };
+    let (before, after) = match (dates("before"), dates("after")) {
+        (Ok(before), Ok(after)) => (before, after),
+        (Err(error), _) | (_, Err(error)) => {
+            println!("ERROR: {error}");
+            return;
         }
-
-        if !&new_files.is_empty() {
-            files = new_files;
-            sort_files = true;
-        } else if new_files.is_empty() && (has_include || has_exclude) {
-            files = vec![];
+    };
+    add(before
+        .into_iter()
+        .map(|d| {
+            boxed(DateFilter {
+                before: Some(d),
+                after: None,
+            })
+        })
+        .collect());
+    add(after
+        .into_iter()
+        .map(|d| {
+            boxed(DateFilter {
+                before: None,
+                after: Some(d),
+            })
+        })
+        .collect());
+
+    let syntax = pattern_syntax(args);
+    let ignore_case = args.get_flag("ignore-case");
+    let patterns = |id: &str| -> Result<Vec<Box<dyn Filter>>, regex::Error> {
+        values(id)
+            .into_iter()
+            .map(|p| {
+                Ok(boxed(NameFilter {
+                    includes: Some(Pattern::new(p, syntax, ignore_case)?),
+                    excludes: None,
+                }))
+            })
+            .collect()
+    };
+    let (includes, excludes) = match (patterns("includes"), patterns("excludes")) {
+        (Ok(includes), Ok(excludes)) => (includes, excludes),
+        (Err(error), _) | (_, Err(error)) => {
+            println!("ERROR: Invalid regex:\n{error}");
+            return;
         }
+    };
+    add(includes);
+    // A file must not include any of the excluded patterns
+    if !excludes.is_empty() {
+        add(vec![boxed(Not::new(Any(excludes)))]);
     }
 
-    // Sort by date
-
-    let re = Regex::new(r"(\d{4})-(\d{2})-(\d{2})").unwrap();
-
-    let mut has_before = false;
-    let mut has_after = false;
-    let mut before: i64 = 0;
-    let mut after: i64 = 0;
-
-    if let Some(ref date_before_val) = date_before {
-        has_before = true;
-        let d = &date_before_val[..];
-        let cap = re.captures(d).unwrap();
-
-        let date_time = chrono::NaiveDate::from_ymd_opt(
-            cap[1].parse::<i32>().unwrap(),
-            cap[2].parse::<u32>().unwrap(),
-            cap[3].parse::<u32>().unwrap(),
-        );
-        let naive_date_time: NaiveDateTime = match date_time {
-            Some(d) => d.and_hms_opt(0, 0, 0).unwrap(),
-            None => {
-                println!("ERROR: Invalid date conversion");
-                return;
-            }
-        };
-        before = naive_date_time.and_utc().timestamp();
-    }
-    if let Some(ref date_after_val) = date_after {
-        has_after = true;
-        let d = &date_after_val[..];
-        let cap = re.captures(d).unwrap();
-        let date_time = chrono::NaiveDate::from_ymd_opt(
-            cap[1].parse::<i32>().unwrap(),
-            cap[2].parse::<u32>().unwrap(),
-            cap[3].parse::<u32>().unwrap(),
-        );
-        let naive_date_time: NaiveDateTime = match date_time {
-            Some(d) => d.and_hms_opt(0, 0, 0).unwrap(),
-            None => {
-                println!("ERROR: Invalid date conversion");
-                return;
-            }
-        };
-
-        after = naive_date_time.and_utc().timestamp();
-    }
-
-    let mut new_files: Vec<Rc<DirEntry>> = vec![];
-    for item in &files {
-        let md = item.metadata().unwrap();
-        //let md = fs::metadata(item)?; // Alternative method
-
-        if md.is_file() {
-            //if let Ok(time) = md.modified() {
-            //    println!("{:?}", time);
-            //} else {
-            //    println!("Not supported on this platform");
-            //}
-
-            let time = md.modified().unwrap().duration_since(UNIX_EPOCH).unwrap(); // TODO: error handling
-            let dur = chrono::Duration::from_std(time).unwrap();
-            let file_date = dur.num_seconds();
-
-            if (!has_after || file_date >= after)
-                && (has_after || has_before)
-                && (file_date <= before || !has_before)
-            {
-                new_files.push(Rc::clone(item));
-            }
+    let sizes = |option: &str| -> Result<Vec<u64>, String> {
+        values(option)
+            .into_iter()
+            .map(|s| {
+                parse_size(s, KB)
+                    .map_err(|error| format!("Invalid size '{s}' for --{option}: {error}"))
+            })
+            .collect()
+    };
+    let (min, max) = match (sizes("min"), sizes("max")) {
+        (Ok(min), Ok(max)) => (min, max),
+        (Err(error), _) | (_, Err(error)) => {
+            println!("ERROR: {error}");
+            return;
         }
-    }
-
-    if !&new_files.is_empty() {
-        files = new_files;
-        sort_files = true;
-    } else if new_files.is_empty() && (has_before || has_after) {
-        files = vec![];
-    }
-
-    // Sort by size
-
-    let mut has_min = false;
-    let mut has_max = false;
-    let mut min: u64 = 0;
-    let mut max: u64 = 0;
-
-    if let Some(ref s) = size_min {
-        has_min = true;
-        min = s.parse::<u64>().unwrap();
-    }
-    if let Some(ref s) = size_max {
-        has_max = true;
-        max = s.parse::<u64>().unwrap();
-    }
-
-    let mut new_files: Vec<Rc<DirEntry>> = vec![];
-    for item in &files {
-        let md = item.metadata().unwrap();
-
-        if md.is_file() {
-            let file_size = &md.len() / 1000; // Convert bytes to kilobytes
-
-            //println!("filesize:{} - min-check:{:?}, max-check:{:?}", &file_size, file_size >= min, file_size <= max);
-            if (!has_max || file_size <= max)
-                && (has_max || has_min)
-                && (file_size >= min || !has_min)
-            {
-                new_files.push(Rc::clone(item));
+    };
+    add(min
+        .into_iter()
+        .map(|min| {
+            boxed(SizeFilter {
+                min: Some(min),
+                max: None,
+            })
+        })
+        .collect());
+    add(max
+        .into_iter()
+        .map(|max| {
+            boxed(SizeFilter {
+                min: None,
+                max: Some(max),
+            })
+        })
+        .collect());
+
+    let detect = match args.get_one::<String>("detect").map(String::as_str) {
+        Some("content") => Detect::Content,
+        Some("both") => Detect::Both,
+        _ => Detect::Extension,
+    };
+    add(values("type")
+        .into_iter()
+        .map(|t| {
+            boxed(
+                TypeFilter::new(t)
+                    .detect(detect)
+                    .case_sensitive(args.get_flag("case-sensitive")),
+            )
+        })
+        .collect());
+
+    let mut expressions: Vec<Box<dyn Filter>> = vec![];
+    for expression in values("where") {
+        match expr::parse(expression) {
+            Ok(filter) => expressions.push(filter),
+            Err(error) => {
+                println!("ERROR: Invalid expression: {}", error.message);
+                println!("  {expression}");
+                println!("  {}^", " ".repeat(error.column - 1));
+                return;
             }
         }
     }
+    add(expressions);
 
-    if !&new_files.is_empty() {
-        files = new_files;
-        sort_files = true;
-    } else if new_files.is_empty() && (has_min || has_max) {
-        files = vec![];
+    // No options were provided
+    if criteria.is_empty() {
+        println!("ERROR: At least one option must be provided");
+        return;
     }
+    let filter: Box<dyn Filter> = if args.get_flag("any") {
+        Box::new(Any(criteria))
+    } else {
+        Box::new(All(criteria))
+    };
 
-    // Sort by type
-
-    if file_type.is_some() {
-        let mut new_files: Vec<Rc<DirEntry>> = vec![];
-        for item in &files {
-            let md = item.metadata().unwrap();
-
-            if md.is_file() {
-                let filename = &item.file_name();
-                let extension = Path::new(filename).extension().and_then(OsStr::to_str);
-                let ext: String = match extension {
-                    Some(f) => f.to_string(),
-                    None => continue,
-                };
-                if ext == file_type.clone().unwrap() {
-                    new_files.push(Rc::clone(item));
-                }
-            }
-        }
-
-        if !&new_files.is_empty() {
-            files = new_files;
-            sort_files = true;
-        } else if new_files.is_empty() && file_type.is_some() {
-            files = vec![];
-        }
-    }
+    let dir = ui::walk(path.as_ref().unwrap(), &[], ctx);
+    let paths_parent = path.as_ref().unwrap().display().to_string();
+    let parent = path.unwrap();
+    println!("CURRENT PATH: {}", &paths_parent);
 
-    if !sort_files {
-        files = vec![];
-    }
+    let files: Vec<Entry> = dir.into_iter().filter(|e| filter.matches(e)).collect();
 
+    let files = remove_nested(files);
     if files.is_empty() {
         println!("There are no files to sort that match the given parameters");
         return;
@@ -419,10 +301,14 @@ pub fn exec(args: &ArgMatches) {
     }
 
     let full_path = parent.clone();
-    let full_path = match util::create_folder(full_path, folder, false) {
+    let full_path = match ui::create_folder(full_path, folder, false, ctx) {
         Ok(f) => f,
         Err(_) => return,
     };
 
-    util::sort_files_rc(&full_path, &files);
+    ui::sort_files(&full_path, &files, ctx);
+}
+
+fn boxed(filter: impl Filter + 'static) -> Box<dyn Filter> {
+    Box::new(filter)
 }
@@ -1,12 +1,14 @@
-use std::ffi::OsStr;
-use std::fs::{self, DirEntry};
 use std::path::PathBuf;
 
+use cabinet::filter::{All, Any, Filter, NameFilter, Not, Pattern, Syntax};
+use cabinet::sort::name::Template;
+use cabinet::util;
+use cabinet::util::walk::{remove_nested, Entry};
+use cabinet::Plan;
 use clap::{Arg, ArgMatches, Command};
-use regex::Regex;
 
-use crate::util;
-use crate::util::path::get_path;
+use crate::context::Context;
+use crate::ui::{self, get_path};
 
 pub fn cli() -> Command {
     Command::new("name")
@@ -16,19 +18,35 @@ pub fn cli() -> Command {
                 .short('I')
                 .long("includes")
                 .value_name("match")
-                .help("File name includes...")
-                .action(clap::ArgAction::Set),
+                .help("File name includes... (can be given more than once, for any of them)")
+                .action(clap::ArgAction::Append),
             Arg::new("excludes")
                 .short('E')
                 .long("excludes")
                 .value_name("match")
-                .help("File name excludes...")
-                .action(clap::ArgAction::Set),
+                .help("File name excludes... (can be given more than once, for all of them)")
+                .action(clap::ArgAction::Append),
             Arg::new("regex")
                 .short('R')
                 .long("regex")
                 .help("Use regular expressions (regex) for pattern matching")
                 .action(clap::ArgAction::SetTrue),
+            Arg::new("into")
+                .long("into")
+                .value_name("folder")
+                .help("Sort each file into a folder named after the groups captured by the regex, e.g. '{1}' or '{client}'")
+                .requires("regex")
+                .action(clap::ArgAction::Set),
+            Arg::new("glob")
+                .long("glob")
+                .help("Use glob patterns (e.g. 'IMG_*.jpg') that match the whole file name")
+                .conflicts_with("regex")
+                .action(clap::ArgAction::SetTrue),
+            Arg::new("ignore-case")
+                .short('i')
+                .long("ignore-case")
+                .help("Match patterns regardless of case")
+                .action(clap::ArgAction::SetTrue),
             Arg::new("template")
                 .short('t')
                 .long("template")
@@ -45,127 +63,112 @@ pub fn cli() -> Command {
         .subcommand_value_name("PATH")
 }
 
-pub fn exec(args: &ArgMatches) {
+pub fn exec(args: &ArgMatches, ctx: &mut Context) {
     let mut path: Option<PathBuf> = None;
     let use_template = args.get_flag("template");
 
     if let Some(p) = args.get_one::<String>("path") {
-        path = get_path(p, use_template);
+        path = get_path(p, use_template, &ctx.config);
     }
     if path.is_none() {
         println!("ERROR: The path is invalid");
         return;
     }
 
-    let mut include_pattern: Option<String> = None;
-    let mut exclude_pattern: Option<String> = None;
-    let use_regex = args.get_flag("regex");
-
-    if let Some(incl) = args.get_one::<String>("includes") {
-        include_pattern = Some(incl.to_string());
-    }
-    if let Some(excl) = args.get_one::<String>("excludes") {
-        exclude_pattern = Some(excl.to_string());
-    }
+    let values = |id: &str| -> Vec<&String> {
+        match args.get_many::<String>(id) {
+            Some(values) => values.collect(),
+            None => vec![],
+        }
+    };
+    let include_patterns = values("includes");
+    let exclude_patterns = values("excludes");
 
     // Neither was provided
-    if include_pattern.is_none() && exclude_pattern.is_none() {
-        println!("ERROR: A before or after date must be provided");
+    if include_patterns.is_empty() && exclude_patterns.is_empty() {
+        println!("ERROR: An includes or excludes pattern (or both) must be provided");
         return;
     }
 
-    let dir = fs::read_dir(path.as_ref().unwrap()).unwrap();
-    let paths_parent = path.as_ref().unwrap().display().to_string();
-    let parent = path.unwrap();
-    println!("CURRENT PATH: {}", &paths_parent);
-
-    let mut files: Vec<DirEntry> = vec![];
-
-    let mut has_include = false;
-    let mut has_exclude = false;
-
-    if use_regex {
-        let mut include: Regex = Regex::new("").unwrap();
-        let mut exclude: Regex = Regex::new("").unwrap();
-
-        if let Some(p) = include_pattern {
-            has_include = true;
-            include = Regex::new(p.as_str()).unwrap();
-        }
-        if let Some(p) = exclude_pattern {
-            has_exclude = true;
-            exclude = Regex::new(p.as_str()).unwrap();
+    let syntax = pattern_syntax(args);
+    let ignore_case = args.get_flag("ignore-case");
+    let compile = |patterns: Vec<&String>| -> Result<Vec<Pattern>, regex::Error> {
+        patterns
+            .into_iter()
+            .map(|p| Pattern::new(p, syntax, ignore_case))
+            .collect()
+    };
+    let (includes, excludes) = match (compile(include_patterns), compile(exclude_patterns)) {
+        (Ok(includes), Ok(excludes)) => (includes, excludes),
+        (Err(error), _) | (_, Err(error)) => {
+            println!("ERROR: Invalid regex:\n{error}");
+            return;
         }
+    };
 
-        for item in dir {
-            let item = item.unwrap();
-            let md = item.metadata().unwrap();
-
-            let filename = &item.file_name();
-            let f = OsStr::to_str(filename).unwrap();
-
-            if md.is_file() {
-                if has_include && has_exclude {
-                    match include.captures(f) {
-                        Some(_) => {}
-                        None => continue,
-                    };
-                    match exclude.captures(f) {
-                        Some(_) => {}
-                        None => continue,
-                    };
-                    files.push(item);
-                } else if has_include && !has_exclude {
-                    match include.captures(f) {
-                        Some(_) => {}
-                        None => continue,
-                    };
-                    files.push(item);
-                } else if has_exclude && !has_include {
-                    match exclude.captures(f) {
-                        Some(_) => {}
-                        None => continue,
-                    };
-                    files.push(item);
-                }
-            }
+    // A file must include any of the included patterns, and none of the excluded ones
+    let any = |patterns: &[Pattern]| {
+        let mut any = Any::new();
+        for p in patterns {
+            any.push(NameFilter {
+                includes: Some(p.clone()),
+                excludes: None,
+            });
         }
-    } else {
-        let mut include = String::new();
-        let mut exclude = String::new();
+        any
+    };
+    let mut filter = All::new();
+    if !includes.is_empty() {
+        filter.push(any(&includes));
+    }
+    if !excludes.is_empty() {
+        filter.push(Not::new(any(&excludes)));
+    }
 
-        if let Some(p) = include_pattern {
-            has_include = true;
-            include = p;
-        }
-        if let Some(p) = exclude_pattern {
-            has_exclude = true;
-            exclude = p;
+    // The folder for each file can be made from the groups captured by the included patterns
+    let mut into: Option<Template> = None;
+    if let Some(template) = args.get_one::<String>("into") {
+        if includes.is_empty() {
+            println!("ERROR: --into needs an includes pattern to capture the folder names with");
+            return;
         }
-
-        for item in dir {
-            let item = item.unwrap();
-            let md = item.metadata().unwrap();
-
-            let filename = &item.file_name();
-            let f = OsStr::to_str(filename).unwrap();
-
-            if md.is_file()
-                && ((has_include && has_exclude && f.contains(&include) && !f.contains(&exclude))
-                    || (has_include && !has_exclude && f.contains(&include))
-                    || (has_exclude && !has_include && !f.contains(&exclude)))
-            {
-                files.push(item);
+        let template = match Template::parse(template) {
+            Ok(t) => t,
+            Err(error) => {
+                println!("ERROR: Invalid folder name '{template}' for --into. {error}");
+                return;
+            }
+        };
+        for pattern in &includes {
+            if let Pattern::Regex(re) = pattern {
+                if let Err(error) = template.check(re) {
+                    println!("ERROR: {error}");
+                    return;
+                }
             }
         }
+        into = Some(template);
     }
 
+    let dir = ui::walk(path.as_ref().unwrap(), &[], ctx);
+    let paths_parent = path.as_ref().unwrap().display().to_string();
+    let parent = path.unwrap();
+    println!("CURRENT PATH: {}", &paths_parent);
+
+    let files: Vec<Entry> = dir.into_iter().filter(|e| filter.matches(e)).collect();
+
+    let files = remove_nested(files);
     if files.is_empty() {
         println!("There are no files to sort that match the given parameters");
         return;
     }
     println!("Found {} files that are able to be sorted", &files.len());
 
+    if let Some(template) = into {
+        sort_into(args, &template, &includes, parent, &files, ctx);
+        return;
+    }
+
     let mut folder = util::set_folder_name("Sorted_by_Name".to_string());
 
     if let Some(out_name) = args.get_one::<String>("output") {
@@ -175,10 +178,68 @@ pub fn exec(args: &ArgMatches) {
     }
 
     let full_path = parent.clone();
-    let full_path = match util::create_folder(full_path, folder, false) {
+    let full_path = match ui::create_folder(full_path, folder, false, ctx) {
         Ok(f) => f,
         Err(_) => return,
     };
 
-    util::sort_files(&full_path, &files);
+    ui::sort_files(&full_path, &files, ctx);
+}
+
+/// Sort each file into the folder made from the groups captured in its name
+fn sort_into(
+    args: &ArgMatches,
+    template: &Template,
+    includes: &[Pattern],
+    parent: PathBuf,
+    files: &[Entry],
+    ctx: &mut Context,
+) {
+    let root = match ui::output_root(args, parent, ctx) {
+        Some(r) => r,
+        None => return,
+    };
+
+    // Files that are already in their folder are not sorted again
+    let mut plan = Plan::default();
+    let mut left = 0;
+    for e in files {
+        let filename = e.file_name().to_string_lossy().to_string();
+        let folder = includes.iter().find_map(|pattern| match pattern {
+            Pattern::Regex(re) => template.folder(re, &filename).map(|f| root.join(f)),
+            Pattern::Text(_) => None,
+        });
+        match folder {
+            Some(f) if e.path().parent() == Some(&f) => {}
+            Some(f) => plan.push(e, &f),
+            None => left += 1,
+        }
+    }
+    if left > 0 {
+        println!(
+            "NOTE: {left} files were left where they are, as their folder name would be empty"
+        );
+    }
+    if plan.is_empty() {
+        println!("There are no files to sort that match the given parameters");
+        return;
+    }
+    println!(
+        "{} files will be sorted into {} folders",
+        plan.len(),
+        plan.folders.len()
+    );
+
+    ui::run_plan(&plan, plan.len(), ctx);
+}
+
+/// The syntax of the file name patterns, as given by `--regex` or `--glob`
+pub fn pattern_syntax(args: &ArgMatches) -> Syntax {
+    if args.get_flag("regex") {
+        Syntax::Regex
+    } else if args.get_flag("glob") {
+        Syntax::Glob
+    } else {
+        Syntax::Text
+    }
 }
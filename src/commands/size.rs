@@ -1,26 +1,42 @@
-use std::fs::{self, DirEntry};
 use std::path::PathBuf;
 
+use cabinet::filter::{Filter, SizeFilter};
+use cabinet::sort::size::{parse_size, Buckets, KB};
+use cabinet::util;
+use cabinet::util::walk::{remove_nested, Entry};
+use cabinet::Plan;
 use clap::{Arg, ArgMatches, Command};
 
-use crate::util;
-use crate::util::path::get_path;
+use crate::context::Context;
+use crate::ui::{self, get_path};
 
 pub fn cli() -> Command {
     Command::new("size")
-        .about("Sort files by their size in KB (do not include 'KB' in the actual command)")
+        .about("Sort files by their size")
         .args([
             Arg::new("min")
                 .short('m')
                 .long("min")
                 .value_name("size")
-                .help("Get files that are GREATER THAN the specified size (in KB)")
+                .help("Get files that are GREATER THAN the specified size, e.g. 500KB or 1.5GiB (in KB without a unit)")
                 .action(clap::ArgAction::Set),
             Arg::new("max")
                 .short('M')
                 .long("max")
                 .value_name("size")
-                .help("Get files that are LESS THAN the specified size (in KB)")
+                .help("Get files that are LESS THAN the specified size, e.g. 500KB or 1.5GiB (in KB without a unit)")
+                .action(clap::ArgAction::Set),
+            Arg::new("buckets")
+                .short('b')
+                .long("buckets")
+                .value_name("sizes")
+                .help("Sort every file into a folder for its size range, split at the given sizes, e.g. 1MB,100MB,1GB")
+                .action(clap::ArgAction::Set),
+            Arg::new("labels")
+                .long("labels")
+                .value_name("names")
+                .help("Folder names to use for the size ranges of --buckets, from smallest to largest, e.g. small,medium,large,huge")
+                .requires("buckets")
                 .action(clap::ArgAction::Set),
             Arg::new("template")
                 .short('t')
@@ -38,12 +54,12 @@ pub fn cli() -> Command {
         .subcommand_value_name("PATH")
 }
 
-pub fn exec(args: &ArgMatches) {
+pub fn exec(args: &ArgMatches, ctx: &mut Context) {
     let mut path: Option<PathBuf> = None;
     let use_template = args.get_flag("template");
 
     if let Some(p) = args.get_one::<String>("path") {
-        path = get_path(p, use_template);
+        path = get_path(p, use_template, &ctx.config);
     }
     if path.is_none() {
         println!("ERROR: The path is invalid");
@@ -60,56 +76,74 @@ pub fn exec(args: &ArgMatches) {
         size_max = Some(max.to_string());
     }
 
-    // Neither was provided
-    if size_min.is_none() && size_max.is_none() {
-        println!("ERROR: A min or max size (or both) must be provided");
-        return;
+    let mut buckets = None;
+    if let Some(sizes) = args.get_one::<String>("buckets") {
+        let mut b = match Buckets::parse(sizes, KB) {
+            Ok(b) => b,
+            Err(error) => {
+                println!("ERROR: Invalid buckets: {error}");
+                return;
+            }
+        };
+        if let Some(labels) = args.get_one::<String>("labels") {
+            let labels = labels.split(',').map(|l| l.trim().to_string()).collect();
+            if let Err(error) = b.set_labels(labels) {
+                println!("ERROR: Invalid labels: {error}");
+                return;
+            }
+        }
+        buckets = Some(b);
     }
 
-    let mut has_min = false;
-    let mut has_max = false;
-    let mut min: u64 = 0;
-    let mut max: u64 = 0;
-
-    if let Some(ref s) = size_min {
-        has_min = true;
-        min = s.parse::<u64>().unwrap();
-    }
-    if let Some(ref s) = size_max {
-        has_max = true;
-        max = s.parse::<u64>().unwrap();
+    // None were provided
+    if size_min.is_none() && size_max.is_none() && buckets.is_none() {
+        println!("ERROR: A min or max size (or both), or --buckets, must be provided");
+        return;
     }
 
-    let dir = fs::read_dir(path.as_ref().unwrap()).unwrap();
+    let size = |s: Option<String>, option: &str| -> Result<Option<u64>, String> {
+        s.map(|s| {
+            parse_size(&s, KB)
+                .map_err(|error| format!("Invalid size '{s}' for --{option}: {error}"))
+        })
+        .transpose()
+    };
+    let filter = match (size(size_min, "min"), size(size_max, "max")) {
+        (Ok(min), Ok(max)) => SizeFilter { min, max },
+        (Err(error), _) | (_, Err(error)) => {
+            println!("ERROR: {error}");
+            return;
+        }
+    };
+
+    // Files that have already been sorted into a size range are not sorted again
+    let skip: Vec<PathBuf> = match &buckets {
+        Some(b) => b
+            .labels
+            .iter()
+            .map(|l| path.as_ref().unwrap().join(l))
+            .collect(),
+        None => vec![],
+    };
+    let dir = ui::walk(path.as_ref().unwrap(), &skip, ctx);
     let paths_parent = path.as_ref().unwrap().display().to_string();
     let parent = path.unwrap();
     println!("CURRENT PATH: {}", &paths_parent);
 
-    let mut files: Vec<DirEntry> = vec![];
-
-    for item in dir {
-        let item = item.unwrap();
-        let md = item.metadata().unwrap();
-
-        if md.is_file() {
-            let file_size = &md.len() / 1000; // Convert bytes to kilobytes
-
-            //println!("filesize:{} - min-check:{:?}, max-check:{:?}", &file_size, file_size >= min, file_size <= max);
-            if (!has_max || file_size <= max)
-                && (has_max || has_min)
-                && (file_size >= min || !has_min)
-            {
-                files.push(item);
-            }
-        }
-    }
+    let files: Vec<Entry> = dir.into_iter().filter(|e| filter.matches(e)).collect();
 
+    let files = remove_nested(files);
     if files.is_empty() {
         println!("There are no files to sort that match the given parameters");
         return;
     }
     println!("Found {} files that are able to be sorted", &files.len());
 
+    if let Some(buckets) = buckets {
+        sort_into_buckets(args, &buckets, parent, &files, ctx);
+        return;
+    }
+
     let mut folder = util::set_folder_name("Sorted_by_Size".to_string());
 
     if let Some(out_name) = args.get_one::<String>("output") {
@@ -119,10 +153,38 @@ pub fn exec(args: &ArgMatches) {
     }
 
     let full_path = parent.clone();
-    let full_path = match util::create_folder(full_path, folder, false) {
+    let full_path = match ui::create_folder(full_path, folder, false, ctx) {
         Ok(f) => f,
         Err(_) => return,
     };
 
-    util::sort_files(&full_path, &files);
+    ui::sort_files(&full_path, &files, ctx);
+}
+
+/// Sort each file into the folder for its size range. The folders are created in the folder
+/// being sorted, or in the output folder if one is given.
+fn sort_into_buckets(
+    args: &ArgMatches,
+    buckets: &Buckets,
+    parent: PathBuf,
+    files: &[Entry],
+    ctx: &mut Context,
+) {
+    let root = match ui::output_root(args, parent, ctx) {
+        Some(r) => r,
+        None => return,
+    };
+
+    let plan = Plan::new(files, |e| Some(root.join(buckets.label(e.size()))));
+    for label in &buckets.labels {
+        let count = files
+            .iter()
+            .filter(|e| buckets.label(e.size()) == label)
+            .count();
+        if count > 0 {
+            println!("  {label}: {count} files");
+        }
+    }
+
+    ui::run_plan(&plan, plan.len(), ctx);
 }
@@ -0,0 +1,310 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use cabinet::util;
+use cabinet::util::journal::{self, Entry, Run};
+use clap::{Arg, ArgMatches, Command};
+
+pub fn cli() -> Command {
+    Command::new("undo")
+        .about("Revert a previous run by moving its files back to where they came from")
+        .args([
+            Arg::new("run")
+                .action(clap::ArgAction::Set)
+                .value_name("RUN_ID")
+                .help("The run to revert (see 'cab history'). Defaults to the most recent run"),
+            Arg::new("force")
+                .short('f')
+                .long("force")
+                .help("Move files back even if they were modified after the run")
+                .action(clap::ArgAction::SetTrue),
+        ])
+}
+
+/// Find the run to revert: either the one with the given id, or the most recent run that has
+/// not already been undone
+pub fn find_run(runs: Vec<Run>, id: Option<&String>) -> Option<Run> {
+    match id {
+        Some(id) => runs.into_iter().find(|r| &r.id == id),
+        None => runs.into_iter().find(|r| r.undone.is_none()),
+    }
+}
+
+pub fn exec(args: &ArgMatches) {
+    let history = match journal::load_all() {
+        Ok(h) => h,
+        Err(error) => {
+            println!("ERROR: Unable to read the run history:\n{:?}", error);
+            return;
+        }
+    };
+    for warning in &history.warnings {
+        println!("WARNING: {warning}");
+    }
+    let runs = history.runs;
+
+    let id = args.get_one::<String>("run");
+    let mut run = match find_run(runs, id) {
+        Some(r) => r,
+        None => {
+            match id {
+                Some(id) => println!("ERROR: There is no run with the id '{id}'"),
+                None => println!("There are no runs left to undo"),
+            }
+            return;
+        }
+    };
+
+    if run.undone.is_some() {
+        println!("ERROR: Run '{}' has already been undone", &run.id);
+        return;
+    }
+
+    let force = args.get_flag("force");
+    let dry_run = args.get_flag("dry-run");
+    let verify = args.get_flag("verify");
+    println!("Undoing run {}: {}", &run.id, run.command_line());
+
+    let mut restored = 0;
+    let mut changed: Vec<PathBuf> = vec![];
+    let mut removed: Vec<PathBuf> = vec![];
+    let mut occupied: Vec<PathBuf> = vec![];
+    let mut failed: Vec<(PathBuf, io::Error)> = vec![];
+    let mut kept_folders: Vec<PathBuf> = vec![];
+    // Files that a dry run would have moved back or removed by now
+    let mut freed: HashSet<PathBuf> = HashSet::new();
+
+    // Revert in reverse order so that folders are emptied before they are removed
+    for entry in run.entries.iter().rev() {
+        match entry {
+            Entry::Move {
+                source,
+                destination,
+                size,
+                mtime,
+            } => {
+                let md = match fs::symlink_metadata(destination) {
+                    Ok(md) => md,
+                    Err(_) => {
+                        // Restored by an earlier, partial undo of this run
+                        if fs::symlink_metadata(source).is_ok_and(|md| md.len() == *size) {
+                            restored += 1;
+                        } else {
+                            removed.push(destination.clone());
+                        }
+                        continue;
+                    }
+                };
+                if !force && (md.len() != *size || journal::mtime_secs(&md) != *mtime) {
+                    changed.push(destination.clone());
+                    continue;
+                }
+                if fs::symlink_metadata(source).is_ok() {
+                    occupied.push(source.clone());
+                    continue;
+                }
+
+                if dry_run {
+                    println!("{} -> {}", destination.display(), source.display());
+                    freed.insert(destination.clone());
+                    restored += 1;
+                    continue;
+                }
+
+                if let Some(parent) = source.parent() {
+                    if let Err(error) = fs::create_dir_all(parent) {
+                        failed.push((destination.clone(), error));
+                        continue;
+                    }
+                }
+                match util::rename(destination, source, verify) {
+                    Ok(_) => restored += 1,
+                    Err(error) => failed.push((destination.clone(), error)),
+                }
+            }
+            Entry::Create {
+                destination,
+                size,
+                mtime,
+                ..
+            } => {
+                let md = match fs::symlink_metadata(destination) {
+                    Ok(md) => md,
+                    // Already removed, so there is nothing left to revert
+                    Err(_) => {
+                        restored += 1;
+                        continue;
+                    }
+                };
+                if !force && (md.len() != *size || journal::mtime_secs(&md) != *mtime) {
+                    changed.push(destination.clone());
+                    continue;
+                }
+
+                if dry_run {
+                    println!("{} would be removed", destination.display());
+                    freed.insert(destination.clone());
+                    restored += 1;
+                    continue;
+                }
+
+                let result = if md.is_dir() {
+                    fs::remove_dir_all(destination)
+                } else {
+                    fs::remove_file(destination)
+                };
+                match result {
+                    Ok(_) => restored += 1,
+                    Err(error) => failed.push((destination.clone(), error)),
+                }
+            }
+            Entry::Remove {
+                path,
+                duplicate_of,
+                size,
+                mtime,
+            } => {
+                if let Ok(md) = fs::symlink_metadata(path) {
+                    // Restored by an earlier, partial undo of this run
+                    if md.len() == *size {
+                        restored += 1;
+                    } else {
+                        occupied.push(path.clone());
+                    }
+                    continue;
+                }
+                let md = match fs::metadata(duplicate_of) {
+                    Ok(md) => md,
+                    Err(_) => {
+                        removed.push(duplicate_of.clone());
+                        continue;
+                    }
+                };
+                if !force && md.len() != *size {
+                    changed.push(duplicate_of.clone());
+                    continue;
+                }
+
+                if dry_run {
+                    println!("{} -> {} (copy)", duplicate_of.display(), path.display());
+                    restored += 1;
+                    continue;
+                }
+
+                match restore_duplicate(path, duplicate_of, *mtime) {
+                    Ok(_) => restored += 1,
+                    Err(error) => failed.push((path.clone(), error)),
+                }
+            }
+            Entry::Replace { path, backup } => {
+                if fs::symlink_metadata(backup).is_err() {
+                    // Unless it was restored by an earlier, partial undo of this run
+                    if fs::symlink_metadata(path).is_err() {
+                        removed.push(backup.clone());
+                    }
+                    continue;
+                }
+                if fs::symlink_metadata(path).is_ok() && !freed.contains(path) {
+                    occupied.push(path.clone());
+                    continue;
+                }
+
+                if dry_run {
+                    println!("{} -> {} (replaced)", backup.display(), path.display());
+                    continue;
+                }
+
+                match util::rename(backup, path, verify) {
+                    Ok(_) => {
+                        // Only removed once everything that was replaced has been restored
+                        if let Some(parent) = backup.parent() {
+                            let _ = fs::remove_dir(parent);
+                        }
+                    }
+                    Err(error) => failed.push((path.clone(), error)),
+                }
+            }
+            Entry::Mkdir { path } if dry_run => {
+                println!("Folder \"{}\" would be removed if empty", path.display())
+            }
+            Entry::Mkdir { path } => match fs::remove_dir(path) {
+                Ok(_) => {}
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(_) => kept_folders.push(path.clone()),
+            },
+        }
+    }
+
+    if dry_run {
+        println!(
+            "Dry run: {}/{} files would be restored. Nothing was changed",
+            restored,
+            run.moved_count()
+        );
+    } else {
+        println!("Restored {}/{} files", restored, run.moved_count());
+    }
+
+    if !changed.is_empty() {
+        println!("\nThe following files were modified after the run and were left in place (use --force to restore them anyway):");
+        for p in &changed {
+            println!("  {}", p.display());
+        }
+    }
+    if !removed.is_empty() {
+        println!("\nThe following files no longer exist and could not be restored:");
+        for p in &removed {
+            println!("  {}", p.display());
+        }
+    }
+    if !occupied.is_empty() {
+        println!("\nThe following original locations are now occupied by another file:");
+        for p in &occupied {
+            println!("  {}", p.display());
+        }
+    }
+    if !failed.is_empty() {
+        println!("\nThere was a problem restoring the following files:");
+        for (p, error) in &failed {
+            println!("  {}: {:?}", p.display(), error);
+        }
+    }
+    if !kept_folders.is_empty() {
+        println!("\nThe following folders are not empty and were left in place:");
+        for p in &kept_folders {
+            println!("  {}", p.display());
+        }
+    }
+
+    // Keep the run available for another attempt if some files can still be restored
+    if dry_run {
+        return;
+    }
+    if changed.is_empty() && occupied.is_empty() && failed.is_empty() {
+        if let Err(error) = run.mark_undone() {
+            println!("WARNING: Unable to mark the run as undone:\n{:?}", error);
+        }
+    } else {
+        println!(
+            "\nRun '{}' has not been fully undone. Resolve the files above and run 'cab undo {}' again",
+            &run.id, &run.id
+        );
+    }
+}
+
+/// Restore a file that was removed as a duplicate by copying the file it was identical to
+fn restore_duplicate(path: &Path, duplicate_of: &Path, mtime: i64) -> Result<(), io::Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(duplicate_of, path)?;
+
+    let time = match u64::try_from(mtime) {
+        Ok(secs) => UNIX_EPOCH + Duration::from_secs(secs),
+        Err(_) => UNIX_EPOCH - Duration::from_secs(mtime.unsigned_abs()),
+    };
+    File::options().write(true).open(path)?.set_modified(time)
+}
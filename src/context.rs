@@ -0,0 +1,41 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use cabinet::util::config::Config;
+use cabinet::util::walk::WalkOptions;
+use cabinet::Executor;
+use clap::ArgMatches;
+
+use crate::args;
+
+/// Options and state shared by everything that happens during a single run of a command
+pub struct Context {
+    /// Assume yes to any prompts, such as using a folder that already exists
+    pub assume_yes: bool,
+    /// Never prompt; fail with `EXIT_PROMPT_REQUIRED` instead
+    pub non_interactive: bool,
+    pub config: Config,
+    pub walk: WalkOptions,
+    pub executor: Executor,
+    /// Folders that a dry run has already said would be created, so they are only listed once
+    pub announced: HashSet<PathBuf>,
+}
+
+impl Context {
+    /// Create the context from the global options and the loaded config file
+    pub fn from_args(args: &ArgMatches, config: Config) -> Result<Context, String> {
+        Ok(Context {
+            assume_yes: args.get_flag("yes"),
+            non_interactive: args.get_flag("non-interactive"),
+            config,
+            walk: args::walk_options(args)?,
+            executor: Executor::new(args::executor_options(args)),
+            announced: HashSet::new(),
+        })
+    }
+
+    /// Only print what would be done, without touching anything on disk
+    pub fn dry_run(&self) -> bool {
+        self.executor.options.dry_run
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::plan::{Plan, Step};
+use crate::util::action::Action;
+use crate::util::conflict::{self, OnConflict, Resolution};
+use crate::util::journal::Journal;
+use crate::util::{copy, rename};
+
+/// How an `Executor` sorts files
+#[derive(Clone, Copy)]
+pub struct Options {
+    pub action: Action,
+    pub on_conflict: OnConflict,
+    /// Compare the contents of files copied to another file system before removing the originals
+    pub verify: bool,
+    /// Only work out what would happen, without changing anything on disk
+    pub dry_run: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            action: Action::Move,
+            on_conflict: OnConflict::Rename,
+            verify: false,
+            dry_run: false,
+        }
+    }
+}
+
+/// What happened (or would happen, in a dry run) to a single file
+pub enum Outcome {
+    /// The file was moved, copied or linked to this path
+    Sorted(PathBuf),
+    /// The file was left where it is, as a file with the same name already exists in the
+    /// destination
+    Skipped,
+    /// The file was removed, as it is identical to this file in the destination
+    Duplicate(PathBuf),
+}
+
+/// The number of files handled by `Executor::execute`
+#[derive(Default)]
+pub struct Report {
+    /// Files that were sorted, including duplicates that were removed
+    pub sorted: usize,
+    pub skipped: usize,
+    pub duplicates: usize,
+    pub failed: usize,
+    /// Problems that did not stop any file from being sorted, such as the journal not being
+    /// writable
+    pub warnings: Vec<String>,
+}
+
+/// Carries out plans, recording every change in the journal so that it can be undone with
+/// `cab undo`
+#[derive(Default)]
+pub struct Executor {
+    pub options: Options,
+    journal: Journal,
+    /// In a dry run, the destinations used so far and the files that would be moved to them
+    planned: HashMap<PathBuf, PathBuf>,
+}
+
+impl Executor {
+    pub fn new(options: Options) -> Executor {
+        Executor {
+            options,
+            journal: Journal::new(),
+            planned: HashMap::new(),
+        }
+    }
+
+    /// Do not record anything in the journal, meaning the changes cannot be undone with
+    /// `cab undo`
+    pub fn without_journal(mut self) -> Executor {
+        self.journal = Journal::disabled();
+        self
+    }
+
+    /// Create a folder. In a dry run, nothing is created.
+    pub fn create_folder(&mut self, path: &Path) -> Result<(), io::Error> {
+        if self.options.dry_run {
+            return Ok(());
+        }
+        fs::create_dir(path)?;
+        self.journal.record_mkdir(path);
+        Ok(())
+    }
+
+    /// Create a folder, along with any of its parent folders that do not exist yet
+    pub fn create_folder_all(&mut self, path: &Path) -> Result<(), io::Error> {
+        if path.as_os_str().is_empty() || path.exists() {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            self.create_folder_all(parent)?;
+        }
+        self.create_folder(path)
+    }
+
+    /// Sort every file in the plan, creating any destination folders that do not exist yet.
+    /// `progress` is called after each step with the index of the step and its outcome.
+    pub fn execute(
+        &mut self,
+        plan: &Plan,
+        mut progress: impl FnMut(usize, &Step, &Result<Outcome, io::Error>),
+    ) -> Report {
+        for folder in &plan.folders {
+            // If this fails, sorting the files into the folder fails as well and is reported then
+            let _ = self.create_folder_all(folder);
+        }
+
+        let mut report = Report::default();
+        for (idx, step) in plan.steps.iter().enumerate() {
+            let outcome = self.sort_file(&step.source, &step.destination);
+            match &outcome {
+                Ok(Outcome::Sorted(_)) => report.sorted += 1,
+                Ok(Outcome::Skipped) => report.skipped += 1,
+                Ok(Outcome::Duplicate(_)) => {
+                    report.sorted += 1;
+                    report.duplicates += 1;
+                }
+                Err(_) => report.failed += 1,
+            }
+            progress(idx, step, &outcome);
+        }
+        // Includes problems recording any folders created before the plan was executed
+        report.warnings = std::mem::take(&mut self.journal.warnings);
+        report
+    }
+
+    /// Move a file to `dest` (or copy or link it, depending on the action), recording the change
+    /// in the journal so it can be undone.
+    ///
+    /// If something already exists at `dest`, the conflict policy decides what happens.
+    pub fn sort_file(&mut self, source: &Path, dest: &Path) -> Result<Outcome, io::Error> {
+        let action = self.options.action;
+        let policy = self.options.on_conflict;
+        let resolution = if self.options.dry_run {
+            conflict::resolve_planned(source, dest, policy, &self.planned)?
+        } else {
+            conflict::resolve(source, dest, policy)?
+        };
+        let dest = match resolution {
+            Resolution::Move(d) => d,
+            Resolution::Skip => return Ok(Outcome::Skipped),
+            // The original is never removed when copying or linking
+            Resolution::Duplicate if action != Action::Move => return Ok(Outcome::Skipped),
+            Resolution::Duplicate => {
+                if !self.options.dry_run {
+                    let md = fs::symlink_metadata(source)?;
+                    fs::remove_file(source)?;
+                    self.journal.record_remove(source, dest, &md);
+                }
+                return Ok(Outcome::Duplicate(dest.to_path_buf()));
+            }
+        };
+
+        if self.options.dry_run {
+            self.planned.insert(dest.clone(), source.to_path_buf());
+            return Ok(Outcome::Sorted(dest));
+        }
+
+        // A file or folder that is being replaced is moved out of the way first, as nothing can be
+        // renamed onto a folder that is not empty
+        let replaced = match fs::symlink_metadata(&dest) {
+            Ok(_) => Some(self.set_aside(&dest)?),
+            Err(_) => None,
+        };
+        if let Err(error) = action.perform(source, &dest, self.options.verify) {
+            if let Some((old, _)) = replaced {
+                let _ = rename(&old, &dest, false);
+            }
+            return Err(error);
+        }
+        match replaced {
+            Some((backup, true)) => self.journal.record_replace(&dest, &backup),
+            Some((old, false)) => copy::remove_all(&old),
+            None => {}
+        }
+
+        match action {
+            Action::Move => self.journal.record_move(source, &dest),
+            a => self.journal.record_create(a.name(), source, &dest),
+        }
+        Ok(Outcome::Sorted(dest))
+    }
+
+    /// Move the file or folder at `dest` out of the way before it is replaced. It is kept in the
+    /// journal so that `cab undo` can restore it, or if there is no journal, next to `dest` until
+    /// it can be removed. Returns where it was moved to, and whether it was kept.
+    fn set_aside(&mut self, dest: &Path) -> Result<(PathBuf, bool), io::Error> {
+        if let Some(backup) = self.journal.set_aside(dest)? {
+            return Ok((backup, true));
+        }
+        let old = copy::old_path(dest);
+        copy::remove_all(&old);
+        fs::rename(dest, &old)?;
+        Ok((old, false))
+    }
+}
@@ -0,0 +1,557 @@
+//! A small expression language for picking files, as used by `cab multisort --where`:
+//!
+//! ```text
+//! ext in (jpg, png) and size > 2MB and modified < 30d ago
+//! not name contains thumb or (name matches '^IMG_\d+' and size >= 1.5MiB)
+//! ```
+//!
+//! Comparisons are combined with `and`, `or` and `not`, and can be grouped with parentheses.
+//! `and` binds more tightly than `or`. The attributes that can be compared are:
+//! - `name` - the file name, with `=`, `!=`, `contains`, `matches` (a regex) or `in (...)`
+//! - `ext` - the file type, with `=`, `!=` or `in (...)`
+//! - `mime` - the MIME type of the contents of the file, such as `image/jpeg` or `image/*`, with
+//!   `=`, `!=` or `in (...)`
+//! - `size` - the size, with `=`, `!=`, `<`, `<=`, `>` or `>=` and a size such as `2MB`
+//! - `mtime` (or `modified`) - the date of modification, compared like the size with a date
+//!   such as `2024-03-15`, `yesterday` or `30d ago`
+//!
+//! `in` can be negated with `not in`. Values that contain spaces or any of `(),'"<>=!` must be
+//! quoted with `'` or `"`.
+
+use std::fmt;
+
+use regex::Regex;
+
+use super::{
+    All, Any, DateFilter, Filter, MimeFilter, NameFilter, Not, Pattern, SizeFilter, TypeFilter,
+};
+use crate::sort::date::parse_period;
+use crate::sort::size::parse_size;
+
+/// A problem with an expression, found at `column` (counting from 1)
+#[derive(Debug)]
+pub struct ParseError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (column {})", self.message, self.column)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse an expression into a filter
+pub fn parse(input: &str) -> Result<Box<dyn Filter>, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+    };
+    let filter = parser.or()?;
+    if parser.peek().token != Token::End {
+        return parser.expected("'and', 'or' or the end of the expression");
+    }
+    Ok(filter)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(PartialEq)]
+enum Token {
+    Word(String),
+    /// A quoted value
+    Text(String),
+    Op(Op),
+    LParen,
+    RParen,
+    Comma,
+    End,
+}
+
+struct Tok {
+    token: Token,
+    /// The text of the token as it was written
+    text: String,
+    column: usize,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Tok>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let token = match c {
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '\'' | '"' => {
+                let Some(len) = chars[i + 1..].iter().position(|&q| q == c) else {
+                    return Err(ParseError {
+                        column: start + 1,
+                        message: format!("Missing closing {c}"),
+                    });
+                };
+                i += len + 1;
+                Token::Text(chars[start + 1..i].iter().collect())
+            }
+            '<' | '>' | '=' | '!' => {
+                let eq = chars.get(i + 1) == Some(&'=');
+                let op = match (c, eq) {
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    ('>', true) => Op::Ge,
+                    ('=', _) => Op::Eq,
+                    ('!', true) => Op::Ne,
+                    _ => {
+                        return Err(ParseError {
+                            column: start + 1,
+                            message: "Expected '!='".to_string(),
+                        })
+                    }
+                };
+                if eq {
+                    i += 1;
+                }
+                Token::Op(op)
+            }
+            _ => {
+                while i + 1 < chars.len()
+                    && !chars[i + 1].is_whitespace()
+                    && !"(),'\"<>=!".contains(chars[i + 1])
+                {
+                    i += 1;
+                }
+                Token::Word(chars[start..=i].iter().collect())
+            }
+        };
+        i += 1;
+
+        tokens.push(Tok {
+            token,
+            text: chars[start..i].iter().collect(),
+            column: start + 1,
+        });
+    }
+
+    tokens.push(Tok {
+        token: Token::End,
+        text: String::new(),
+        column: chars.len() + 1,
+    });
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy)]
+enum Attr {
+    Name,
+    Ext,
+    Mime,
+    Size,
+    Mtime,
+}
+
+enum Cmp {
+    Op(Op),
+    In,
+    Contains,
+    Matches,
+}
+
+struct Parser {
+    tokens: Vec<Tok>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Tok {
+        &self.tokens[self.pos]
+    }
+
+    fn advance(&mut self) {
+        if self.peek().token != Token::End {
+            self.pos += 1;
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().token, Token::Word(w) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        let found = self.peek().token == token;
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn expected<T>(&self, what: &str) -> Result<T, ParseError> {
+        let tok = self.peek();
+        let message = match tok.token {
+            Token::End => format!("Expected {what}, but the expression ended"),
+            _ => format!("Expected {what}, found '{}'", tok.text),
+        };
+        Err(ParseError {
+            column: tok.column,
+            message,
+        })
+    }
+
+    fn or(&mut self) -> Result<Box<dyn Filter>, ParseError> {
+        let mut filters = vec![self.and()?];
+        while self.eat_keyword("or") {
+            filters.push(self.and()?);
+        }
+        if filters.len() == 1 {
+            return Ok(filters.remove(0));
+        }
+        Ok(Box::new(Any(filters)))
+    }
+
+    fn and(&mut self) -> Result<Box<dyn Filter>, ParseError> {
+        let mut filters = vec![self.unary()?];
+        while self.eat_keyword("and") {
+            filters.push(self.unary()?);
+        }
+        if filters.len() == 1 {
+            return Ok(filters.remove(0));
+        }
+        Ok(Box::new(All(filters)))
+    }
+
+    fn unary(&mut self) -> Result<Box<dyn Filter>, ParseError> {
+        if self.eat_keyword("not") {
+            return Ok(Box::new(Not(self.unary()?)));
+        }
+        if self.eat(Token::LParen) {
+            let filter = self.or()?;
+            if !self.eat(Token::RParen) {
+                return self.expected("')'");
+            }
+            return Ok(filter);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Box<dyn Filter>, ParseError> {
+        let attr = match &self.peek().token {
+            Token::Word(w) => match w.to_lowercase().as_str() {
+                "name" => Attr::Name,
+                "ext" | "extension" | "type" => Attr::Ext,
+                "mime" => Attr::Mime,
+                "size" => Attr::Size,
+                "mtime" | "modified" => Attr::Mtime,
+                _ => return self.expected("name, ext, mime, size or mtime"),
+            },
+            _ => return self.expected("name, ext, mime, size or mtime"),
+        };
+        let attr_text = self.peek().text.clone();
+        self.advance();
+
+        let op_column = self.peek().column;
+        let negate = self.eat_keyword("not");
+        let cmp = match &self.peek().token {
+            _ if self.is_keyword("in") => Cmp::In,
+            _ if negate => return self.expected("'in'"),
+            Token::Op(op) => Cmp::Op(*op),
+            _ if self.is_keyword("contains") => Cmp::Contains,
+            _ if self.is_keyword("matches") => Cmp::Matches,
+            _ => {
+                return self
+                    .expected("'=', '!=', '<', '<=', '>', '>=', 'in', 'contains' or 'matches'")
+            }
+        };
+        let op_text = self.peek().text.clone();
+        self.advance();
+
+        let filter: Box<dyn Filter> = match (attr, cmp) {
+            (Attr::Name | Attr::Ext | Attr::Mime, Cmp::In) => {
+                let values = self.list()?;
+                Box::new(Any(values.iter().map(|v| equals(attr, v)).collect()))
+            }
+            (Attr::Name | Attr::Ext | Attr::Mime, Cmp::Op(Op::Eq)) => {
+                equals(attr, &self.value()?.0)
+            }
+            (Attr::Name | Attr::Ext | Attr::Mime, Cmp::Op(Op::Ne)) => {
+                Box::new(Not(equals(attr, &self.value()?.0)))
+            }
+            (Attr::Name, Cmp::Contains) => Box::new(NameFilter {
+                includes: Some(Pattern::Text(self.value()?.0)),
+                excludes: None,
+            }),
+            (Attr::Name, Cmp::Matches) => {
+                let (value, column) = self.value()?;
+                // Only keep the description of the problem, as the regex is pointed at already
+                let re = Regex::new(&value).map_err(|error| ParseError {
+                    column,
+                    message: match error.to_string().lines().last() {
+                        Some(line) => {
+                            format!("Invalid regex: {}", line.trim_start_matches("error: "))
+                        }
+                        None => "Invalid regex".to_string(),
+                    },
+                })?;
+                Box::new(NameFilter {
+                    includes: Some(Pattern::Regex(re)),
+                    excludes: None,
+                })
+            }
+            (Attr::Size, Cmp::Op(op)) => {
+                let (value, column) = self.words()?;
+                let bytes =
+                    parse_size(&value, 1).map_err(|message| ParseError { column, message })?;
+                compare_size(op, bytes)
+            }
+            (Attr::Mtime, Cmp::Op(op)) => {
+                let (value, column) = self.words()?;
+                let (start, end) = parse_period(&value).map_err(|error| ParseError {
+                    column,
+                    message: format!("Invalid date '{value}'. {error}"),
+                })?;
+                compare_date(op, start, end)
+            }
+            _ => {
+                return Err(ParseError {
+                    column: op_column,
+                    message: format!("'{op_text}' cannot be used with '{attr_text}'"),
+                })
+            }
+        };
+
+        if negate {
+            return Ok(Box::new(Not(filter)));
+        }
+        Ok(filter)
+    }
+
+    /// A single value, along with its column
+    fn value(&mut self) -> Result<(String, usize), ParseError> {
+        let tok = self.peek();
+        let value = match &tok.token {
+            Token::Word(w) | Token::Text(w) => (w.clone(), tok.column),
+            _ => return self.expected("a value"),
+        };
+        self.advance();
+        Ok(value)
+    }
+
+    /// A value that may be written as several words, such as `2 MB` or `3 weeks ago`
+    fn words(&mut self) -> Result<(String, usize), ParseError> {
+        if let Token::Text(_) = self.peek().token {
+            return self.value();
+        }
+        let column = self.peek().column;
+        let mut words = vec![];
+        while !self.is_keyword("and") && !self.is_keyword("or") {
+            match &self.peek().token {
+                Token::Word(w) => words.push(w.clone()),
+                _ => break,
+            }
+            self.advance();
+        }
+        if words.is_empty() {
+            return self.expected("a value");
+        }
+        Ok((words.join(" "), column))
+    }
+
+    /// A list of values in parentheses, such as `(jpg, png)`. A single value does not need
+    /// parentheses.
+    fn list(&mut self) -> Result<Vec<String>, ParseError> {
+        if !self.eat(Token::LParen) {
+            return Ok(vec![self.value()?.0]);
+        }
+        let mut values = vec![self.value()?.0];
+        while self.eat(Token::Comma) {
+            values.push(self.value()?.0);
+        }
+        if !self.eat(Token::RParen) {
+            return self.expected("',' or ')'");
+        }
+        Ok(values)
+    }
+}
+
+/// Match files whose name, file type or MIME type is exactly `value`
+fn equals(attr: Attr, value: &str) -> Box<dyn Filter> {
+    match attr {
+        Attr::Ext => Box::new(TypeFilter::new(value.trim_start_matches('.'))),
+        Attr::Mime => Box::new(MimeFilter {
+            mime: value.to_string(),
+        }),
+        _ => {
+            let re = Regex::new(&format!("^{}$", regex::escape(value))).unwrap();
+            Box::new(NameFilter {
+                includes: Some(Pattern::Regex(re)),
+                excludes: None,
+            })
+        }
+    }
+}
+
+fn compare_size(op: Op, bytes: u64) -> Box<dyn Filter> {
+    let range = |min, max| Box::new(SizeFilter { min, max });
+    match op {
+        Op::Eq => range(Some(bytes), Some(bytes)),
+        Op::Ne => Box::new(Not(range(Some(bytes), Some(bytes)))),
+        // Nothing is smaller than 0 bytes
+        Op::Lt if bytes == 0 => Box::new(Any::new()),
+        Op::Lt => range(None, Some(bytes - 1)),
+        Op::Le => range(None, Some(bytes)),
+        Op::Gt => range(Some(bytes.saturating_add(1)), None),
+        Op::Ge => range(Some(bytes), None),
+    }
+}
+
+/// Compare the date of modification with the time between `start` and `end` (inclusive), such
+/// as a whole day
+fn compare_date(op: Op, start: i64, end: i64) -> Box<dyn Filter> {
+    let range = |after, before| Box::new(DateFilter { before, after });
+    match op {
+        Op::Eq => range(Some(start), Some(end)),
+        Op::Ne => Box::new(Not(range(Some(start), Some(end)))),
+        Op::Lt => range(None, Some(start - 1)),
+        Op::Le => range(None, Some(end)),
+        Op::Gt => range(Some(end + 1), None),
+        Op::Ge => range(Some(start), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::util::walk::{walk, WalkOptions};
+
+    /// Create the given files (name and size) in a new folder, and get the names of those that
+    /// match the expression
+    fn matching(expr: &str, files: &[(&str, usize)]) -> Vec<String> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "cabinet-expr-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, size) in files {
+            fs::write(dir.join(name), vec![b'x'; *size]).unwrap();
+        }
+
+        let filter = parse(expr).unwrap();
+        let mut names: Vec<String> = walk(&dir, &WalkOptions::default(), &[])
+            .entries
+            .iter()
+            .filter(|e| filter.matches(e))
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        fs::remove_dir_all(&dir).unwrap();
+        names
+    }
+
+    fn error(expr: &str) -> ParseError {
+        match parse(expr) {
+            Ok(_) => panic!("'{expr}' should not parse"),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn and_binds_more_tightly_than_or() {
+        let files = [("a.txt", 1), ("b.txt", 1), ("c.txt", 20)];
+        assert_eq!(
+            matching("name = a.txt or name = b.txt and size > 10", &files),
+            ["a.txt"]
+        );
+        assert_eq!(
+            matching("(name = a.txt or name = b.txt) and size > 10", &files),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            matching("size > 10 and name = c.txt or name = a.txt", &files),
+            ["a.txt", "c.txt"]
+        );
+    }
+
+    #[test]
+    fn not_in() {
+        let files = [("a.txt", 1), ("b.md", 1), ("c.pdf", 1)];
+        assert_eq!(matching("ext not in (txt, md)", &files), ["c.pdf"]);
+        assert_eq!(matching("not ext in (txt, md)", &files), ["c.pdf"]);
+        assert_eq!(matching("ext in (txt, md)", &files), ["a.txt", "b.md"]);
+        assert_eq!(matching("name not in a.txt", &files), ["b.md", "c.pdf"]);
+    }
+
+    #[test]
+    fn values_of_several_words_end_at_and_or() {
+        let files = [("a.txt", 1), ("b.txt", 3000)];
+        assert_eq!(
+            matching("mtime > 3 weeks ago and name = a.txt", &files),
+            ["a.txt"]
+        );
+        assert_eq!(
+            matching("modified < 3 weeks ago or name = b.txt", &files),
+            ["b.txt"]
+        );
+        assert_eq!(matching("size < 2 KB and ext = txt", &files), ["a.txt"]);
+    }
+
+    #[test]
+    fn error_columns() {
+        let e = error("name = 'abc");
+        assert_eq!(e.column, 8);
+        assert_eq!(e.message, "Missing closing '");
+
+        let e = error("size > 1MB and colour = red");
+        assert_eq!(e.column, 16);
+        assert_eq!(
+            e.message,
+            "Expected name, ext, mime, size or mtime, found 'colour'"
+        );
+
+        let e = error("ext contains jp");
+        assert_eq!(e.column, 5);
+        assert_eq!(e.message, "'contains' cannot be used with 'ext'");
+
+        let e = error("size not in (1, 2)");
+        assert_eq!(e.column, 6);
+        assert_eq!(e.message, "'in' cannot be used with 'size'");
+
+        let e = error("(name = a or name = b");
+        assert_eq!(e.column, 22);
+        assert_eq!(e.message, "Expected ')', but the expression ended");
+
+        let e = error("ext in (jpg, png");
+        assert_eq!(e.column, 17);
+        assert_eq!(e.message, "Expected ',' or ')', but the expression ended");
+    }
+}
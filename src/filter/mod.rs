@@ -0,0 +1,410 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::sort::date::modified_timestamp;
+use crate::sort::file_type::{normalise, Detect};
+use crate::util::walk::Entry;
+
+pub mod expr;
+
+/// Decides whether a file (or folder) should be sorted
+pub trait Filter {
+    fn matches(&self, entry: &Entry) -> bool;
+}
+
+impl<F: Filter + ?Sized> Filter for Box<F> {
+    fn matches(&self, entry: &Entry) -> bool {
+        (**self).matches(entry)
+    }
+}
+
+/// Match entries that match every one of the filters. If there are no filters, every entry
+/// matches.
+#[derive(Default)]
+pub struct All(pub Vec<Box<dyn Filter>>);
+
+impl All {
+    pub fn new() -> All {
+        All::default()
+    }
+
+    /// Add a filter that must also match
+    pub fn with(mut self, filter: impl Filter + 'static) -> All {
+        self.push(filter);
+        self
+    }
+
+    pub fn push(&mut self, filter: impl Filter + 'static) {
+        self.0.push(Box::new(filter));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Filter for All {
+    fn matches(&self, entry: &Entry) -> bool {
+        self.0.iter().all(|f| f.matches(entry))
+    }
+}
+
+/// Match entries that match at least one of the filters. If there are no filters, no entry
+/// matches.
+#[derive(Default)]
+pub struct Any(pub Vec<Box<dyn Filter>>);
+
+impl Any {
+    pub fn new() -> Any {
+        Any::default()
+    }
+
+    /// Add a filter that can match instead
+    pub fn or(mut self, filter: impl Filter + 'static) -> Any {
+        self.push(filter);
+        self
+    }
+
+    pub fn push(&mut self, filter: impl Filter + 'static) {
+        self.0.push(Box::new(filter));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Filter for Any {
+    fn matches(&self, entry: &Entry) -> bool {
+        self.0.iter().any(|f| f.matches(entry))
+    }
+}
+
+/// Match entries that do not match the filter
+pub struct Not(pub Box<dyn Filter>);
+
+impl Not {
+    pub fn new(filter: impl Filter + 'static) -> Not {
+        Not(Box::new(filter))
+    }
+}
+
+impl Filter for Not {
+    fn matches(&self, entry: &Entry) -> bool {
+        !self.0.matches(entry)
+    }
+}
+
+/// How a file name pattern is written
+#[derive(Clone, Copy, PartialEq)]
+pub enum Syntax {
+    /// Text that is somewhere in the name
+    Text,
+    /// A glob that matches the whole name, such as `IMG_*.jpg`. `*` matches any number of
+    /// characters, `?` matches one character, `[abc]` (or `[!abc]`) matches one of (or none of)
+    /// the characters in the brackets, and `{jpg,png}` matches any of the comma-separated
+    /// alternatives.
+    Glob,
+    /// A regular expression that is somewhere in the name
+    Regex,
+}
+
+/// A file name pattern, either a plain substring or a regular expression
+#[derive(Clone)]
+pub enum Pattern {
+    Text(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    /// Read a pattern written in the given syntax. Globs, and any pattern that ignores case, are
+    /// turned into a regular expression.
+    pub fn new(pattern: &str, syntax: Syntax, ignore_case: bool) -> Result<Pattern, regex::Error> {
+        let regex = match syntax {
+            Syntax::Text if !ignore_case => return Ok(Pattern::Text(pattern.to_string())),
+            Syntax::Text => regex::escape(pattern),
+            Syntax::Glob => glob_to_regex(pattern),
+            Syntax::Regex => pattern.to_string(),
+        };
+        let re = RegexBuilder::new(&regex)
+            .case_insensitive(ignore_case)
+            .build()?;
+        Ok(Pattern::Regex(re))
+    }
+
+    pub fn is_match(&self, filename: &str) -> bool {
+        match self {
+            Pattern::Text(t) => filename.contains(t),
+            Pattern::Regex(re) => re.is_match(filename),
+        }
+    }
+}
+
+/// Turn a glob into a regular expression that matches the same names. Brackets and braces that
+/// are not closed are matched as they are.
+fn glob_to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::from("^");
+    let mut in_braces = false;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                let negate = chars.get(i + 1) == Some(&'!');
+                let first = if negate { i + 2 } else { i + 1 };
+                // A `]` straight after the `[` (or `[!`) is part of the set
+                match chars.iter().skip(first + 1).position(|&c| c == ']') {
+                    Some(len) => {
+                        regex.push('[');
+                        if negate {
+                            regex.push('^');
+                        }
+                        for &c in &chars[first..first + 1 + len] {
+                            if matches!(c, '\\' | '[' | ']' | '^' | '&' | '~') {
+                                regex.push('\\');
+                            }
+                            regex.push(c);
+                        }
+                        regex.push(']');
+                        i = first + 1 + len;
+                    }
+                    None => regex.push_str(r"\["),
+                }
+            }
+            '{' if !in_braces && chars[i..].contains(&'}') => {
+                in_braces = true;
+                regex.push_str("(?:");
+            }
+            ',' if in_braces => regex.push('|'),
+            '}' if in_braces => {
+                in_braces = false;
+                regex.push(')');
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    regex.push('$');
+    regex
+}
+
+/// Match entries by whether their name includes and/or excludes a pattern
+#[derive(Default)]
+pub struct NameFilter {
+    pub includes: Option<Pattern>,
+    pub excludes: Option<Pattern>,
+}
+
+impl NameFilter {
+    pub fn is_empty(&self) -> bool {
+        self.includes.is_none() && self.excludes.is_none()
+    }
+}
+
+impl Filter for NameFilter {
+    fn matches(&self, entry: &Entry) -> bool {
+        let filename = entry.file_name().to_string_lossy().to_string();
+        self.includes.as_ref().is_none_or(|p| p.is_match(&filename))
+            && self
+                .excludes
+                .as_ref()
+                .is_none_or(|p| !p.is_match(&filename))
+    }
+}
+
+/// Match entries by their date of modification. Both dates are Unix timestamps and are
+/// inclusive.
+#[derive(Default)]
+pub struct DateFilter {
+    pub before: Option<i64>,
+    pub after: Option<i64>,
+}
+
+impl DateFilter {
+    pub fn is_empty(&self) -> bool {
+        self.before.is_none() && self.after.is_none()
+    }
+}
+
+impl Filter for DateFilter {
+    fn matches(&self, entry: &Entry) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let file_date = match modified_timestamp(entry.metadata()) {
+            Some(d) => d,
+            None => return false,
+        };
+        self.before.is_none_or(|before| file_date <= before)
+            && self.after.is_none_or(|after| file_date >= after)
+    }
+}
+
+/// Match entries by their size in bytes. Both sizes are inclusive.
+#[derive(Default)]
+pub struct SizeFilter {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
+impl SizeFilter {
+    pub fn is_empty(&self) -> bool {
+        self.min.is_none() && self.max.is_none()
+    }
+}
+
+impl Filter for SizeFilter {
+    fn matches(&self, entry: &Entry) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let file_size = entry.size();
+        self.min.is_none_or(|min| file_size >= min) && self.max.is_none_or(|max| file_size <= max)
+    }
+}
+
+/// Match files by their file type, which is their extension unless `detect` says otherwise.
+/// File types are not case-sensitive unless `case_sensitive` is set. Folders do not have a file
+/// type, so they never match.
+pub struct TypeFilter {
+    pub file_type: String,
+    pub detect: Detect,
+    pub case_sensitive: bool,
+}
+
+impl TypeFilter {
+    pub fn new(file_type: &str) -> TypeFilter {
+        TypeFilter {
+            file_type: file_type.to_string(),
+            detect: Detect::Extension,
+            case_sensitive: false,
+        }
+    }
+
+    /// Take the file type of each file from its contents or extension, as given by `detect`
+    pub fn detect(mut self, detect: Detect) -> TypeFilter {
+        self.detect = detect;
+        self
+    }
+
+    /// Only match extensions with the same case, so that `jpg` does not match `photo.JPG`
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> TypeFilter {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+}
+
+impl Filter for TypeFilter {
+    fn matches(&self, entry: &Entry) -> bool {
+        let file_type = self.detect.file_type(entry, self.case_sensitive);
+        if file_type == Some(normalise(&self.file_type, self.case_sensitive)) {
+            return true;
+        }
+        // A type found from the contents matches any of the extensions that type can have, e.g.
+        // `jpeg` as well as `jpg`
+        match entry.content_type() {
+            Some(c)
+                if self.detect != Detect::Extension
+                    && file_type.as_deref() == Some(c.file_type) =>
+            {
+                c.has_extension(&self.file_type)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Match files by the MIME type of their contents, such as `image/jpeg`. A MIME type ending in
+/// `/*`, such as `image/*`, matches every type under it. Files whose contents are not recognised
+/// never match.
+pub struct MimeFilter {
+    pub mime: String,
+}
+
+impl Filter for MimeFilter {
+    fn matches(&self, entry: &Entry) -> bool {
+        let Some(content) = entry.content_type() else {
+            return false;
+        };
+        match self.mime.strip_suffix("/*") {
+            Some(kind) => content
+                .mime
+                .split_once('/')
+                .is_some_and(|(k, _)| k.eq_ignore_ascii_case(kind)),
+            None => content.mime.eq_ignore_ascii_case(&self.mime),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::*;
+
+    #[test]
+    fn globs() {
+        // The glob, the regex it is turned into, and names that do and do not match it
+        let cases: &[(&str, &str, &[&str], &[&str])] = &[
+            (
+                "*.txt",
+                r"^.*\.txt$",
+                &["a.txt", ".txt"],
+                &["a.txt.bak", "atxt"],
+            ),
+            (
+                "IMG_????.jpg",
+                r"^IMG_....\.jpg$",
+                &["IMG_0001.jpg"],
+                &["IMG_01.jpg"],
+            ),
+            ("[abc]*", r"^[abc].*$", &["a", "cat"], &["dog", ""]),
+            ("[a-c]", r"^[a-c]$", &["b"], &["d", "-"]),
+            ("[!abc]*", r"^[^abc].*$", &["dog"], &["cat", ""]),
+            ("[]x]", r"^[\]x]$", &["]", "x"], &["[", "]x"]),
+            ("[!]x]", r"^[^\]x]$", &["a"], &["]", "x"]),
+            ("[^x]", r"^[\^x]$", &["^", "x"], &["a"]),
+            ("a[b", r"^a\[b$", &["a[b"], &["ab"]),
+            ("[]", r"^\[\]$", &["[]"], &["", "]"]),
+            ("[!", r"^\[!$", &["[!"], &["!"]),
+            (
+                "{jpg,png}",
+                r"^(?:jpg|png)$",
+                &["jpg", "png"],
+                &["gif", "{jpg,png}"],
+            ),
+            (
+                "*.{jpg,png}",
+                r"^.*\.(?:jpg|png)$",
+                &["a.jpg", "b.png"],
+                &["a.gif", "a.jpgx"],
+            ),
+            ("a{b", r"^a\{b$", &["a{b"], &["ab"]),
+            ("a}b", r"^a\}b$", &["a}b"], &["ab"]),
+            ("a,b", r"^a,b$", &["a,b"], &["a", "b"]),
+            ("a.b+c", r"^a\.b\+c$", &["a.b+c"], &["axbbc"]),
+        ];
+
+        for (glob, expected, matches, non_matches) in cases {
+            let regex = glob_to_regex(glob);
+            assert_eq!(&regex, expected, "{glob}");
+
+            let re = Regex::new(&regex).unwrap();
+            for name in *matches {
+                assert!(re.is_match(name), "{glob} should match {name}");
+            }
+            for name in *non_matches {
+                assert!(!re.is_match(name), "{glob} should not match {name}");
+            }
+        }
+    }
+
+    #[test]
+    fn glob_patterns_ignore_case() {
+        let pattern = Pattern::new("*.JPG", Syntax::Glob, true).unwrap();
+        assert!(pattern.is_match("photo.jpg"));
+        let pattern = Pattern::new("*.JPG", Syntax::Glob, false).unwrap();
+        assert!(!pattern.is_match("photo.jpg"));
+    }
+}
@@ -0,0 +1,40 @@
+//! Cabinet is a convenient file sorting utility. This library contains everything the `cab`
+//! command line tool is built on, so that the same sorting can be done from other programs.
+//!
+//! Sorting is split into four steps:
+//! - [`walk`] scans a directory and returns an [`Entry`] for every file (or folder) found
+//! - the types in [`filter`] pick the entries to sort by their name, date, size or type
+//! - a [`Plan`] maps each entry to the folder it should be sorted into
+//! - an [`Executor`] carries out the plan, moving, copying or linking each file and recording
+//!   the changes in a journal so they can be undone (unless it is created with
+//!   [`Executor::without_journal`])
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use cabinet::filter::{Filter, TypeFilter};
+//! use cabinet::{walk, Executor, Plan, WalkOptions};
+//!
+//! let root = Path::new("/home/user/Downloads");
+//! let filter = TypeFilter::new("pdf");
+//! let files: Vec<_> = walk(root, &WalkOptions::default(), &[])
+//!     .entries
+//!     .into_iter()
+//!     .filter(|e| filter.matches(e))
+//!     .collect();
+//!
+//! let plan = Plan::into_folder(&files, &root.join("PDFs"));
+//! let report = Executor::default().execute(&plan, |_, _, _| {});
+//! println!("Sorted {} files", report.sorted);
+//! ```
+
+pub mod execute;
+pub mod filter;
+pub mod plan;
+pub mod sort;
+pub mod util;
+
+pub use crate::execute::{Executor, Outcome, Report};
+pub use crate::filter::Filter;
+pub use crate::plan::{Plan, Step};
+pub use crate::util::walk::{walk, Entry, WalkOptions};
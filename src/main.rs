@@ -1,20 +1,21 @@
 use std::env;
+use std::process::exit;
 
 use clap::{Arg, Command};
 
+mod args;
 mod commands;
-mod sort;
-mod util;
+mod context;
+mod ui;
+
+use cabinet::util::config::Config;
+use context::Context;
 
 const NAME: &str = "Cabinet";
 const BIN_NAME: &str = "cab";
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const ABOUT: &str = "A convenient file sorting utility";
 
-// TODO: Add option for config file (e.g. add your own template paths)
-// TODO: Add option to sort folders as well as files
-// TODO: Better handling of symlinks
-// TODO: Add "-y / --yes" flag for assuming yes to prompts (such as folder creation confirmation)
 fn main() {
     let about_text = format!("{} {}\n{}", NAME, VERSION, ABOUT);
     let usage_text = format!("{} <command> [options] [<path>]", BIN_NAME);
@@ -32,21 +33,129 @@ fn main() {
         .override_usage(usage_text)
         .after_help(after_help_text)
         .subcommands(commands::builtin())
-        .args([Arg::new("output")
-            .long("output")
-            .short('o')
-            .help("Specify the name of the output folder")
-            .action(clap::ArgAction::Set)
-            .global(true)]);
+        .args_override_self(true)
+        .args([
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .help("Specify the name of the output folder")
+                .action(clap::ArgAction::Set)
+                .global(true),
+            Arg::new("config")
+                .long("config")
+                .value_name("file")
+                .help("Use the given config file instead of ~/.config/cabinet/config.toml")
+                .action(clap::ArgAction::Set)
+                .global(true),
+            Arg::new("recursive")
+                .long("recursive")
+                .short('r')
+                .help("Also sort files in subfolders")
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
+            Arg::new("max-depth")
+                .long("max-depth")
+                .value_name("N")
+                .help("Only sort files up to N levels deep, where 1 is the folder being sorted (implies --recursive)")
+                .value_parser(clap::value_parser!(usize))
+                .action(clap::ArgAction::Set)
+                .global(true),
+            Arg::new("min-depth")
+                .long("min-depth")
+                .value_name("N")
+                .help("Only sort files at least N levels deep, where 1 is the folder being sorted (implies --recursive)")
+                .value_parser(clap::value_parser!(usize))
+                .action(clap::ArgAction::Set)
+                .global(true),
+            Arg::new("entries")
+                .long("entries")
+                .value_name("kind")
+                .help("Sort files, folders (moved as a whole), or both")
+                .value_parser(["files", "dirs", "all"])
+                .default_value("files")
+                .action(clap::ArgAction::Set)
+                .global(true),
+            Arg::new("symlinks")
+                .long("symlinks")
+                .value_name("policy")
+                .help("Skip symbolic links, follow them to what they link to, or move them as links")
+                .value_parser(["skip", "follow", "move-link"])
+                .default_value("skip")
+                .action(clap::ArgAction::Set)
+                .global(true),
+            Arg::new("action")
+                .long("action")
+                .value_name("action")
+                .help("Move the files, or copy or link them into the folders and leave the originals in place")
+                .value_parser(["move", "copy", "hardlink", "symlink", "reflink"])
+                .default_value("move")
+                .action(clap::ArgAction::Set)
+                .global(true),
+            Arg::new("on-conflict")
+                .long("on-conflict")
+                .value_name("policy")
+                .help("What to do when a file with the same name already exists in the destination folder")
+                .value_parser(["skip", "overwrite", "rename", "keep-newer", "keep-larger", "dedupe"])
+                .default_value("rename")
+                .action(clap::ArgAction::Set)
+                .global(true),
+            Arg::new("verify")
+                .long("verify")
+                .help("When moving files to another file system, compare the contents of each copy with the original before removing it")
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .help("Assume yes to all prompts, such as using an output folder that already exists")
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
+            Arg::new("non-interactive")
+                .long("non-interactive")
+                .help("Never prompt; exit with code 3 if a prompt would be needed")
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Print the folders that would be created and the files that would be moved, without changing anything")
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
+        ]);
+
+    let args: Vec<_> = env::args_os().collect();
+    let config = match Config::load(args::config_arg(&args).as_deref()) {
+        Ok(c) => c,
+        Err(error) => {
+            println!("ERROR: {error}");
+            exit(1);
+        }
+    };
+    let args = match args::expand_args(&cabinet, args, &config) {
+        Ok(a) => a,
+        Err(error) => {
+            println!("ERROR: {error}");
+            exit(1);
+        }
+    };
 
-    let matches = cabinet.get_matches();
+    let matches = cabinet.get_matches_from(args);
+    let mut ctx = match Context::from_args(&matches, config) {
+        Ok(c) => c,
+        Err(error) => {
+            println!("ERROR: {error}");
+            exit(1);
+        }
+    };
 
     match matches.subcommand() {
-        Some(("date", cmd)) => commands::date::exec(cmd),
-        Some(("multisort", cmd)) => commands::multisort::exec(cmd),
-        Some(("name", cmd)) => commands::name::exec(cmd),
-        Some(("size", cmd)) => commands::size::exec(cmd),
-        Some(("type", cmd)) => commands::file_type::exec(cmd),
+        Some(("apply", cmd)) => commands::apply::exec(cmd, &mut ctx),
+        Some(("date", cmd)) => commands::date::exec(cmd, &mut ctx),
+        Some(("history", cmd)) => commands::history::exec(cmd),
+        Some(("multisort", cmd)) => commands::multisort::exec(cmd, &mut ctx),
+        Some(("name", cmd)) => commands::name::exec(cmd, &mut ctx),
+        Some(("size", cmd)) => commands::size::exec(cmd, &mut ctx),
+        Some(("type", cmd)) => commands::file_type::exec(cmd, &mut ctx),
+        Some(("undo", cmd)) => commands::undo::exec(cmd),
         _ => unreachable!(),
     }
 }
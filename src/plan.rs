@@ -0,0 +1,56 @@
+use std::path::{Path, PathBuf};
+
+use crate::util::walk::Entry;
+
+/// A single file (or folder) to be sorted into `destination`
+pub struct Step {
+    pub source: PathBuf,
+    /// The full path the file is moved to, including its name
+    pub destination: PathBuf,
+}
+
+/// The folders that files are sorted into, and where each file goes
+#[derive(Default)]
+pub struct Plan {
+    /// Every destination folder, in the order they are first used
+    pub folders: Vec<PathBuf>,
+    pub steps: Vec<Step>,
+}
+
+impl Plan {
+    /// Plan to sort each entry into the folder returned by `folder_for`. Entries for which it
+    /// returns `None` are left where they are.
+    pub fn new(entries: &[Entry], mut folder_for: impl FnMut(&Entry) -> Option<PathBuf>) -> Plan {
+        let mut plan = Plan::default();
+        for entry in entries {
+            if let Some(folder) = folder_for(entry) {
+                plan.push(entry, &folder);
+            }
+        }
+        plan
+    }
+
+    /// Plan to sort every entry into the same folder
+    pub fn into_folder(entries: &[Entry], folder: &Path) -> Plan {
+        Plan::new(entries, |_| Some(folder.to_path_buf()))
+    }
+
+    /// Add an entry to be sorted into `folder`, keeping its name
+    pub fn push(&mut self, entry: &Entry, folder: &Path) {
+        if !self.folders.iter().any(|f| f == folder) {
+            self.folders.push(folder.to_path_buf());
+        }
+        self.steps.push(Step {
+            source: entry.path(),
+            destination: folder.join(entry.file_name()),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
@@ -0,0 +1,395 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// How many bytes at the start of a file are read to work out its type
+const SNIFF_LEN: usize = 8192;
+
+/// A file type recognised from the contents of a file
+pub struct ContentType {
+    /// The file type, as the extension it is usually given, e.g. `jpg`
+    pub file_type: &'static str,
+    /// The MIME type, e.g. `image/jpeg`
+    pub mime: &'static str,
+    /// Every extension that files of this type can have, e.g. `jpg`, `jpeg` and `jfif`
+    pub extensions: &'static [&'static str],
+}
+
+impl ContentType {
+    /// Whether a file with this extension is expected to have contents of this type. Extensions
+    /// are not case-sensitive.
+    pub fn has_extension(&self, extension: &str) -> bool {
+        self.extensions
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case(extension))
+    }
+}
+
+const fn content_type(
+    file_type: &'static str,
+    mime: &'static str,
+    extensions: &'static [&'static str],
+) -> ContentType {
+    ContentType {
+        file_type,
+        mime,
+        extensions,
+    }
+}
+
+const PNG: ContentType = content_type("png", "image/png", &["png", "apng"]);
+const JPG: ContentType = content_type("jpg", "image/jpeg", &["jpg", "jpeg", "jpe", "jfif"]);
+const GIF: ContentType = content_type("gif", "image/gif", &["gif"]);
+const WEBP: ContentType = content_type("webp", "image/webp", &["webp"]);
+const TIFF: ContentType = content_type(
+    "tif",
+    "image/tiff",
+    &["tif", "tiff", "dng", "cr2", "nef", "arw"],
+);
+const PSD: ContentType = content_type("psd", "image/vnd.adobe.photoshop", &["psd"]);
+const HEIC: ContentType = content_type("heic", "image/heic", &["heic", "heif"]);
+const AVIF: ContentType = content_type("avif", "image/avif", &["avif"]);
+const MP4: ContentType = content_type("mp4", "video/mp4", &["mp4", "m4v"]);
+const M4A: ContentType = content_type("m4a", "audio/mp4", &["m4a", "m4b", "mp4"]);
+const MOV: ContentType = content_type("mov", "video/quicktime", &["mov", "qt"]);
+const THREE_GP: ContentType = content_type("3gp", "video/3gpp", &["3gp", "3g2"]);
+const MKV: ContentType = content_type("mkv", "video/x-matroska", &["mkv", "mka", "mks"]);
+const WEBM: ContentType = content_type("webm", "video/webm", &["webm"]);
+const AVI: ContentType = content_type("avi", "video/x-msvideo", &["avi"]);
+const MP3: ContentType = content_type("mp3", "audio/mpeg", &["mp3"]);
+const WAV: ContentType = content_type("wav", "audio/wav", &["wav"]);
+const FLAC: ContentType = content_type("flac", "audio/flac", &["flac"]);
+const OGG: ContentType = content_type("ogg", "audio/ogg", &["ogg", "oga", "ogv", "opus"]);
+const PDF: ContentType = content_type("pdf", "application/pdf", &["pdf", "ai"]);
+const RTF: ContentType = content_type("rtf", "application/rtf", &["rtf"]);
+const DOCX: ContentType = content_type(
+    "docx",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    &["docx", "docm", "dotx"],
+);
+const XLSX: ContentType = content_type(
+    "xlsx",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    &["xlsx", "xlsm", "xltx"],
+);
+const PPTX: ContentType = content_type(
+    "pptx",
+    "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    &["pptx", "pptm", "potx"],
+);
+const EPUB: ContentType = content_type("epub", "application/epub+zip", &["epub"]);
+const ODT: ContentType = content_type("odt", "application/vnd.oasis.opendocument.text", &["odt"]);
+const ODS: ContentType = content_type(
+    "ods",
+    "application/vnd.oasis.opendocument.spreadsheet",
+    &["ods"],
+);
+const ODP: ContentType = content_type(
+    "odp",
+    "application/vnd.oasis.opendocument.presentation",
+    &["odp"],
+);
+const ZIP: ContentType = content_type(
+    "zip",
+    "application/zip",
+    &[
+        "zip", "jar", "apk", "aab", "ipa", "xpi", "whl", "nupkg", "vsix", "cbz",
+    ],
+);
+const GZ: ContentType = content_type("gz", "application/gzip", &["gz", "tgz", "tar.gz"]);
+const BZ2: ContentType = content_type("bz2", "application/x-bzip2", &["bz2", "tbz2", "tar.bz2"]);
+const XZ: ContentType = content_type("xz", "application/x-xz", &["xz", "txz", "tar.xz"]);
+const ZST: ContentType = content_type("zst", "application/zstd", &["zst", "tar.zst"]);
+const SEVEN_Z: ContentType = content_type("7z", "application/x-7z-compressed", &["7z"]);
+const RAR: ContentType = content_type("rar", "application/vnd.rar", &["rar", "cbr"]);
+const TAR: ContentType = content_type("tar", "application/x-tar", &["tar"]);
+const ELF: ContentType = content_type(
+    "elf",
+    "application/x-executable",
+    &["elf", "so", "o", "bin", "appimage"],
+);
+const EXE: ContentType = content_type(
+    "exe",
+    "application/vnd.microsoft.portable-executable",
+    &["exe", "dll", "sys", "efi", "scr"],
+);
+const SQLITE: ContentType = content_type(
+    "sqlite",
+    "application/vnd.sqlite3",
+    &["sqlite", "sqlite3", "db"],
+);
+const WOFF: ContentType = content_type("woff", "font/woff", &["woff"]);
+const WOFF2: ContentType = content_type("woff2", "font/woff2", &["woff2"]);
+
+/// Files that start with these bytes are of the given type. Types that need more than a prefix
+/// to tell them apart, such as ZIP-based documents and MP4 files, are handled in `sniff`.
+const SIGNATURES: &[(&[u8], ContentType)] = &[
+    (b"\x89PNG\r\n\x1a\n", PNG),
+    (b"\xff\xd8\xff", JPG),
+    (b"GIF87a", GIF),
+    (b"GIF89a", GIF),
+    (b"II*\0", TIFF),
+    (b"MM\0*", TIFF),
+    (b"8BPS", PSD),
+    (b"%PDF-", PDF),
+    (b"{\\rtf", RTF),
+    (b"ID3", MP3),
+    (b"fLaC", FLAC),
+    (b"OggS", OGG),
+    (b"\x1f\x8b", GZ),
+    (b"BZh", BZ2),
+    (b"\xfd7zXZ\0", XZ),
+    (b"\x28\xb5\x2f\xfd", ZST),
+    (b"7z\xbc\xaf\x27\x1c", SEVEN_Z),
+    (b"Rar!\x1a\x07", RAR),
+    (b"\x7fELF", ELF),
+    (b"MZ", EXE),
+    (b"SQLite format 3\0", SQLITE),
+    (b"wOFF", WOFF),
+    (b"wOF2", WOFF2),
+];
+
+/// Work out the type of a file from its first bytes, rather than from its extension. Returns
+/// `None` if the type is not recognised, which includes plain text files.
+pub fn detect(path: &Path) -> io::Result<Option<&'static ContentType>> {
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut buf)?;
+    Ok(sniff(&buf))
+}
+
+/// Work out the type of a file from the bytes it starts with
+pub fn sniff(bytes: &[u8]) -> Option<&'static ContentType> {
+    if bytes.starts_with(b"PK\x03\x04") || bytes.starts_with(b"PK\x05\x06") {
+        return Some(zip(bytes));
+    }
+    if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+        return ftyp(&bytes[8..12]);
+    }
+    if bytes.len() >= 12 && bytes.starts_with(b"RIFF") {
+        return match &bytes[8..12] {
+            b"WEBP" => Some(&WEBP),
+            b"WAVE" => Some(&WAV),
+            b"AVI " => Some(&AVI),
+            _ => None,
+        };
+    }
+    if bytes.starts_with(b"\x1a\x45\xdf\xa3") {
+        // Matroska and WebM only differ in the document type given in the header
+        return Some(if contains(bytes, b"webm") {
+            &WEBM
+        } else {
+            &MKV
+        });
+    }
+    if bytes.len() >= 262 && &bytes[257..262] == b"ustar" {
+        return Some(&TAR);
+    }
+    // MP3 files without an ID3 tag start with a frame header
+    if bytes.len() >= 2 && bytes[0] == 0xff && matches!(bytes[1], 0xfb | 0xf3 | 0xf2) {
+        return Some(&MP3);
+    }
+
+    SIGNATURES
+        .iter()
+        .find(|(signature, _)| bytes.starts_with(signature))
+        .map(|(_, content_type)| content_type)
+}
+
+/// Office and OpenDocument files, EPUB books and others are ZIP archives, which are told apart
+/// by the names of the files inside them
+fn zip(bytes: &[u8]) -> &'static ContentType {
+    // OpenDocument files and EPUB books start with an uncompressed `mimetype` file
+    if bytes.len() > 30 && bytes[30..].starts_with(b"mimetype") {
+        let mime = &bytes[38..];
+        for content_type in [&EPUB, &ODT, &ODS, &ODP] {
+            if mime.starts_with(content_type.mime.as_bytes()) {
+                return content_type;
+            }
+        }
+    }
+    if contains(bytes, b"[Content_Types].xml") || contains(bytes, b"_rels/.rels") {
+        for (folder, content_type) in [(&b"word/"[..], &DOCX), (b"xl/", &XLSX), (b"ppt/", &PPTX)] {
+            if contains(bytes, folder) {
+                return content_type;
+            }
+        }
+    }
+    &ZIP
+}
+
+/// MP4, QuickTime, HEIC and similar files, which are told apart by their brand
+fn ftyp(brand: &[u8]) -> Option<&'static ContentType> {
+    match brand {
+        b"qt  " => Some(&MOV),
+        b"M4A " | b"M4B " => Some(&M4A),
+        b"heic" | b"heix" | b"heim" | b"heis" | b"mif1" | b"msf1" => Some(&HEIC),
+        b"avif" | b"avis" => Some(&AVIF),
+        _ if brand.starts_with(b"3g") => Some(&THREE_GP),
+        b"isom" | b"iso2" | b"iso4" | b"iso5" | b"iso6" | b"mp41" | b"mp42" | b"avc1" | b"M4V "
+        | b"M4VH" | b"M4VP" | b"MSNV" | b"dash" | b"f4v " => Some(&MP4),
+        _ => None,
+    }
+}
+
+fn contains(bytes: &[u8], needle: &[u8]) -> bool {
+    bytes.windows(needle.len()).any(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The start of a ZIP archive whose first file is `name`, containing `data`
+    fn zip_with(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut bytes = b"PK\x03\x04".to_vec();
+        bytes.resize(30, 0);
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn ftyp_with(brand: &[u8]) -> Vec<u8> {
+        [b"\0\0\0\x18ftyp", brand, b"\0\0\0\0"].concat()
+    }
+
+    fn riff_with(format: &[u8]) -> Vec<u8> {
+        [b"RIFF\x24\0\0\0", format].concat()
+    }
+
+    type Expected = Option<(&'static str, &'static str)>;
+
+    #[test]
+    fn sniff_types() {
+        let mut tar = vec![0; 262];
+        tar[257..262].copy_from_slice(b"ustar");
+
+        // A description, the bytes, and the file type and MIME type they are recognised as
+        let cases: Vec<(&str, Vec<u8>, Expected)> = vec![
+            (
+                "png",
+                b"\x89PNG\r\n\x1a\n\0\0".to_vec(),
+                Some(("png", "image/png")),
+            ),
+            (
+                "jpeg",
+                b"\xff\xd8\xff\xe0".to_vec(),
+                Some(("jpg", "image/jpeg")),
+            ),
+            ("gif", b"GIF89a".to_vec(), Some(("gif", "image/gif"))),
+            (
+                "pdf",
+                b"%PDF-1.7".to_vec(),
+                Some(("pdf", "application/pdf")),
+            ),
+            (
+                "mp3 with tag",
+                b"ID3\x04".to_vec(),
+                Some(("mp3", "audio/mpeg")),
+            ),
+            (
+                "mp3 frame",
+                b"\xff\xfb\x90".to_vec(),
+                Some(("mp3", "audio/mpeg")),
+            ),
+            (
+                "gzip",
+                b"\x1f\x8b\x08".to_vec(),
+                Some(("gz", "application/gzip")),
+            ),
+            (
+                "elf",
+                b"\x7fELF\x02".to_vec(),
+                Some(("elf", "application/x-executable")),
+            ),
+            ("tar", tar, Some(("tar", "application/x-tar"))),
+            // ZIP archives and the formats built on them
+            (
+                "zip",
+                zip_with("photo.jpg", b""),
+                Some(("zip", "application/zip")),
+            ),
+            (
+                "docx",
+                zip_with("[Content_Types].xml", b"...word/document.xml"),
+                Some(("docx", DOCX.mime)),
+            ),
+            (
+                "xlsx",
+                zip_with("_rels/.rels", b"...xl/workbook.xml"),
+                Some(("xlsx", XLSX.mime)),
+            ),
+            (
+                "pptx",
+                zip_with("[Content_Types].xml", b"...ppt/slides"),
+                Some(("pptx", PPTX.mime)),
+            ),
+            (
+                "odt",
+                zip_with("mimetype", b"application/vnd.oasis.opendocument.text"),
+                Some(("odt", ODT.mime)),
+            ),
+            (
+                "ods",
+                zip_with(
+                    "mimetype",
+                    b"application/vnd.oasis.opendocument.spreadsheet",
+                ),
+                Some(("ods", ODS.mime)),
+            ),
+            (
+                "epub",
+                zip_with("mimetype", b"application/epub+zip"),
+                Some(("epub", "application/epub+zip")),
+            ),
+            (
+                "unknown mimetype",
+                zip_with("mimetype", b"application/x-other"),
+                Some(("zip", "application/zip")),
+            ),
+            // `ftyp` brands
+            ("mp4", ftyp_with(b"isom"), Some(("mp4", "video/mp4"))),
+            ("m4v", ftyp_with(b"M4V "), Some(("mp4", "video/mp4"))),
+            ("mov", ftyp_with(b"qt  "), Some(("mov", "video/quicktime"))),
+            ("m4a", ftyp_with(b"M4A "), Some(("m4a", "audio/mp4"))),
+            ("heic", ftyp_with(b"heic"), Some(("heic", "image/heic"))),
+            ("avif", ftyp_with(b"avif"), Some(("avif", "image/avif"))),
+            ("3gp", ftyp_with(b"3gp5"), Some(("3gp", "video/3gpp"))),
+            ("unknown brand", ftyp_with(b"abcd"), None),
+            // RIFF containers
+            ("webp", riff_with(b"WEBP"), Some(("webp", "image/webp"))),
+            ("wav", riff_with(b"WAVE"), Some(("wav", "audio/wav"))),
+            ("avi", riff_with(b"AVI "), Some(("avi", "video/x-msvideo"))),
+            ("unknown riff", riff_with(b"CDXA"), None),
+            // Matroska
+            (
+                "webm",
+                b"\x1a\x45\xdf\xa3\x9f\x42\x82\x84webm".to_vec(),
+                Some(("webm", "video/webm")),
+            ),
+            (
+                "mkv",
+                b"\x1a\x45\xdf\xa3\x9f\x42\x82\x88matroska".to_vec(),
+                Some(("mkv", "video/x-matroska")),
+            ),
+            // Not recognised
+            ("text", b"Hello, world".to_vec(), None),
+            ("empty", vec![], None),
+            ("short riff", b"RIFF".to_vec(), None),
+        ];
+
+        for (name, bytes, expected) in cases {
+            let found = sniff(&bytes).map(|c| (c.file_type, c.mime));
+            assert_eq!(found, expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn has_extension() {
+        assert!(JPG.has_extension("jpeg"));
+        assert!(JPG.has_extension("JFIF"));
+        assert!(!JPG.has_extension("png"));
+        assert!(GZ.has_extension("tar.gz"));
+    }
+}
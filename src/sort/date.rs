@@ -1 +1,335 @@
-// Unimplemented
+use std::fs::Metadata;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+
+const DATE_FORMATS: &str = "Dates can be written as YYYY-MM-DD, as a date and time (e.g. \
+    2024-03-15T14:30+02:00), as a relative date (e.g. 7d or '3 weeks ago'), or as now, today, \
+    yesterday, or this/last week, month or year";
+
+/// Parse a date into a Unix timestamp. For dates that cover more than a moment, such as a whole
+/// day, this is the start of it. See `parse_period` for the formats that are accepted.
+pub fn parse_date(date: &str) -> Result<i64, String> {
+    parse_period(date).map(|(start, _)| start)
+}
+
+/// Parse a date into the period of time it covers, as a pair of inclusive Unix timestamps.
+/// Accepted are:
+/// - dates (`2024-03-15`), covering the whole day
+/// - dates with a time of day (`2024-03-15 14:30` or `2024-03-15T14:30:00+02:00`). Without an
+///   offset (or `Z`), the time is in UTC
+/// - relative dates counted back from now, such as `7d`, `90 min` or `3 weeks ago`
+/// - `now`, `today`, `yesterday` and `tomorrow`
+/// - `this` or `last` followed by `week` (starting on Monday), `month` or `year`, covering the
+///   whole week, month or year
+///
+/// Like dates, days, weeks, months and years are in UTC. The error describes the accepted
+/// formats, but does not include the date itself.
+pub fn parse_period(value: &str) -> Result<(i64, i64), String> {
+    parse_period_at(value, Utc::now())
+}
+
+/// `parse_period`, with relative dates counted back from `now`
+fn parse_period_at(value: &str, now: DateTime<Utc>) -> Result<(i64, i64), String> {
+    let value = value.trim().to_lowercase();
+    let today = now.date_naive();
+
+    match value.as_str() {
+        "now" => return Ok((now.timestamp(), now.timestamp())),
+        "today" => return Ok(days(today, today)),
+        "yesterday" => return Ok(days(today - Days::new(1), today - Days::new(1))),
+        "tomorrow" => return Ok(days(today + Days::new(1), today + Days::new(1))),
+        _ => {}
+    }
+    if let Some((start, end)) = calendar(&value, today) {
+        return Ok(days(start, end));
+    }
+    if let Some(period) = absolute(&value) {
+        return Ok(period);
+    }
+    let ago = relative(&value, now)?;
+    Ok((ago, ago))
+}
+
+/// The period of time that files are grouped by when each is sorted into a folder for its date
+#[derive(Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    Year,
+    Quarter,
+    Month,
+    Week,
+    Day,
+}
+
+impl GroupBy {
+    /// The folder for files modified at `timestamp`, such as `2024/2024-03` when grouping by
+    /// month. Weeks are ISO weeks (e.g. `2024/2024-W11`) and quarters are numbered from 1 (e.g.
+    /// `2024/2024-Q1`). Like dates, the folders are in UTC.
+    pub fn folder(self, timestamp: i64) -> Option<PathBuf> {
+        let date = DateTime::from_timestamp(timestamp, 0)?.date_naive();
+        let year = PathBuf::from(date.year().to_string());
+        let folder = match self {
+            GroupBy::Year => year,
+            GroupBy::Quarter => year.join(format!("{}-Q{}", date.year(), date.month0() / 3 + 1)),
+            GroupBy::Month => year.join(date.format("%Y-%m").to_string()),
+            GroupBy::Week => {
+                // The first days of January can belong to the last week of the year before
+                let week = date.iso_week();
+                PathBuf::from(week.year().to_string()).join(format!(
+                    "{}-W{:02}",
+                    week.year(),
+                    week.week()
+                ))
+            }
+            GroupBy::Day => year
+                .join(date.format("%Y-%m").to_string())
+                .join(date.format("%Y-%m-%d").to_string()),
+        };
+        Some(folder)
+    }
+}
+
+/// Get the date of modification of a file as a Unix timestamp
+pub fn modified_timestamp(md: &Metadata) -> Option<i64> {
+    let time = md.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(time.as_secs() as i64)
+}
+
+/// The period from the start of the first day to the end of the last day
+fn days(first: NaiveDate, last: NaiveDate) -> (i64, i64) {
+    let start = first.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+    let end = last.and_hms_opt(23, 59, 59).unwrap().and_utc().timestamp();
+    (start, end)
+}
+
+/// The first and last day of `this week`, `last month` and so on
+fn calendar(value: &str, today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    let (which, unit) = value.split_once(' ')?;
+    let back = match which {
+        "this" => 0,
+        "last" => 1,
+        _ => return None,
+    };
+
+    match unit.trim() {
+        "week" => {
+            let monday = today - Days::new(today.weekday().num_days_from_monday() as u64);
+            let first = monday - Days::new(7 * back);
+            Some((first, first + Days::new(6)))
+        }
+        "month" => {
+            let first = today.with_day(1)? - Months::new(back as u32);
+            Some((first, first + Months::new(1) - Days::new(1)))
+        }
+        "year" => {
+            let year = today.year() - back as i32;
+            Some((
+                NaiveDate::from_ymd_opt(year, 1, 1)?,
+                NaiveDate::from_ymd_opt(year, 12, 31)?,
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// A date, or a date with a time of day
+fn absolute(value: &str) -> Option<(i64, i64)> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(days(date, date));
+    }
+
+    // Allow both `2024-03-15T14:30` and `2024-03-15 14:30`, and `Z` for UTC
+    let mut value = value.replacen('t', " ", 1);
+    if let Some(v) = value.strip_suffix('z') {
+        value = format!("{v}+00:00");
+    }
+
+    for format in ["%Y-%m-%d %H:%M:%S%.f%#z", "%Y-%m-%d %H:%M%#z"] {
+        if let Ok(dt) = DateTime::parse_from_str(&value, format) {
+            return Some((dt.timestamp(), dt.timestamp()));
+        }
+    }
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(&value, format) {
+            let t = dt.and_utc().timestamp();
+            return Some((t, t));
+        }
+    }
+    None
+}
+
+/// A time counted back from now, such as `7d` or `3 weeks ago`
+fn relative(value: &str, now: DateTime<Utc>) -> Result<i64, String> {
+    let value = value.strip_suffix("ago").unwrap_or(value).trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let unit = unit.trim();
+    let Ok(amount) = amount.parse::<u32>() else {
+        return Err(DATE_FORMATS.to_string());
+    };
+    if unit.is_empty() || !unit.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(DATE_FORMATS.to_string());
+    }
+
+    let seconds =
+        |s: i64| TimeDelta::try_seconds(s * amount as i64).and_then(|d| now.checked_sub_signed(d));
+    let time = match unit {
+        "s" | "sec" | "secs" | "second" | "seconds" => seconds(1),
+        "min" | "mins" | "minute" | "minutes" => seconds(60),
+        "h" | "hr" | "hrs" | "hour" | "hours" => seconds(60 * 60),
+        "d" | "day" | "days" => seconds(60 * 60 * 24),
+        "w" | "wk" | "wks" | "week" | "weeks" => seconds(60 * 60 * 24 * 7),
+        "mo" | "month" | "months" => now.checked_sub_months(Months::new(amount)),
+        "y" | "yr" | "yrs" | "year" | "years" => amount
+            .checked_mul(12)
+            .and_then(|m| now.checked_sub_months(Months::new(m))),
+        _ => {
+            return Err(format!(
+                "Unknown unit '{unit}'. Use s, min, h, d, w, mo or y, e.g. '3 weeks ago'"
+            ))
+        }
+    };
+    match time {
+        Some(t) => Ok(t.timestamp()),
+        None => Err("The date is too far in the past".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wednesday 2024-03-13 10:30:00 UTC
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-03-13T10:30:00Z")
+            .unwrap()
+            .to_utc()
+    }
+
+    fn period(value: &str) -> (i64, i64) {
+        parse_period_at(value, now()).unwrap()
+    }
+
+    fn timestamp(rfc3339: &str) -> i64 {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().timestamp()
+    }
+
+    /// The whole of the days from `first` to `last`
+    fn between(first: &str, last: &str) -> (i64, i64) {
+        (
+            timestamp(&format!("{first}T00:00:00Z")),
+            timestamp(&format!("{last}T23:59:59Z")),
+        )
+    }
+
+    #[test]
+    fn named_days() {
+        let now = now().timestamp();
+        assert_eq!(period("now"), (now, now));
+        assert_eq!(period("Today"), between("2024-03-13", "2024-03-13"));
+        assert_eq!(period("yesterday"), between("2024-03-12", "2024-03-12"));
+        assert_eq!(period(" tomorrow "), between("2024-03-14", "2024-03-14"));
+    }
+
+    #[test]
+    fn calendar_periods() {
+        assert_eq!(period("this week"), between("2024-03-11", "2024-03-17"));
+        assert_eq!(period("last week"), between("2024-03-04", "2024-03-10"));
+        // 2024 is a leap year
+        assert_eq!(period("this month"), between("2024-03-01", "2024-03-31"));
+        assert_eq!(period("last month"), between("2024-02-01", "2024-02-29"));
+        assert_eq!(period("this year"), between("2024-01-01", "2024-12-31"));
+        assert_eq!(period("last  year"), between("2023-01-01", "2023-12-31"));
+        assert!(parse_period_at("next week", now()).is_err());
+        assert!(parse_period_at("this fortnight", now()).is_err());
+    }
+
+    #[test]
+    fn calendar_periods_across_years() {
+        let now = DateTime::parse_from_rfc3339("2024-01-03T08:00:00Z")
+            .unwrap()
+            .to_utc();
+        assert_eq!(
+            parse_period_at("last week", now),
+            Ok(between("2023-12-25", "2023-12-31"))
+        );
+        assert_eq!(
+            parse_period_at("last month", now),
+            Ok(between("2023-12-01", "2023-12-31"))
+        );
+    }
+
+    #[test]
+    fn absolute_dates() {
+        assert_eq!(period("2024-03-15"), between("2024-03-15", "2024-03-15"));
+
+        let cases = [
+            ("2024-03-15 14:30", "2024-03-15T14:30:00Z"),
+            ("2024-03-15T14:30", "2024-03-15T14:30:00Z"),
+            ("2024-03-15T14:30:20", "2024-03-15T14:30:20Z"),
+            ("2024-03-15T14:30:20.5", "2024-03-15T14:30:20Z"),
+            ("2024-03-15T14:30Z", "2024-03-15T14:30:00Z"),
+            ("2024-03-15t14:30:20z", "2024-03-15T14:30:20Z"),
+            ("2024-03-15T14:30+02:00", "2024-03-15T12:30:00Z"),
+            ("2024-03-15 14:30:20-0500", "2024-03-15T19:30:20Z"),
+        ];
+        for (value, expected) in cases {
+            let t = timestamp(expected);
+            assert_eq!(period(value), (t, t), "{value}");
+        }
+
+        assert!(parse_period_at("2024-02-30", now()).is_err());
+        assert!(parse_period_at("2024-03-15 25:00", now()).is_err());
+    }
+
+    #[test]
+    fn relative_dates() {
+        let now = now();
+        let cases = [
+            ("30s", now - TimeDelta::seconds(30)),
+            ("90 min", now - TimeDelta::minutes(90)),
+            ("2h", now - TimeDelta::hours(2)),
+            ("7d", now - TimeDelta::days(7)),
+            ("1 day", now - TimeDelta::days(1)),
+            ("3 weeks ago", now - TimeDelta::weeks(3)),
+            ("3wago", now - TimeDelta::weeks(3)),
+            (
+                "1mo",
+                DateTime::parse_from_rfc3339("2024-02-13T10:30:00Z")
+                    .unwrap()
+                    .to_utc(),
+            ),
+            (
+                "2 years ago",
+                DateTime::parse_from_rfc3339("2022-03-13T10:30:00Z")
+                    .unwrap()
+                    .to_utc(),
+            ),
+        ];
+        for (value, expected) in cases {
+            let t = expected.timestamp();
+            assert_eq!(parse_period_at(value, now), Ok((t, t)), "{value}");
+        }
+    }
+
+    #[test]
+    fn invalid_relative_dates() {
+        for value in ["", "ago", "d", "7", "-7d", "7.5d", "3 weeks from now"] {
+            assert_eq!(
+                parse_period_at(value, now()),
+                Err(DATE_FORMATS.to_string()),
+                "{value}"
+            );
+        }
+        assert!(parse_period_at("3 fortnights ago", now())
+            .unwrap_err()
+            .starts_with("Unknown unit 'fortnights'"));
+        assert_eq!(
+            parse_period_at("4294967295y", now()),
+            Err("The date is too far in the past".to_string())
+        );
+    }
+}
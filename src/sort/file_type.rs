@@ -1 +1,238 @@
-// Unimplemented
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::sort::content::ContentType;
+use crate::util::walk::Entry;
+
+/// The category of file types that are not in any other category
+pub const OTHER: &str = "Other";
+
+/// The built-in categories and the file types in each
+const CATEGORIES: &[(&str, &[&str])] = &[
+    (
+        "Images",
+        &[
+            "jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "webp", "heic", "heif", "avif",
+            "svg", "ico", "psd", "raw", "cr2", "nef", "arw", "dng",
+        ],
+    ),
+    (
+        "Videos",
+        &[
+            "mp4", "m4v", "mkv", "mov", "avi", "wmv", "flv", "webm", "mpg", "mpeg", "3gp",
+        ],
+    ),
+    (
+        "Audio",
+        &[
+            "mp3", "wav", "flac", "aac", "ogg", "oga", "m4a", "wma", "opus", "aiff", "mid", "midi",
+        ],
+    ),
+    (
+        "Documents",
+        &[
+            "pdf", "doc", "docx", "odt", "rtf", "txt", "md", "xls", "xlsx", "ods", "csv", "ppt",
+            "pptx", "odp", "epub", "pages", "numbers", "key",
+        ],
+    ),
+    (
+        "Archives",
+        &[
+            "zip", "rar", "7z", "tar", "gz", "tgz", "bz2", "xz", "zst", "iso", "tar.gz", "tar.bz2",
+            "tar.xz", "tar.zst", "tar.lz", "tar.lz4", "tar.lzma", "tar.z",
+        ],
+    ),
+    (
+        "Code",
+        &[
+            "rs", "py", "js", "ts", "c", "h", "cpp", "hpp", "cs", "java", "kt", "go", "rb", "php",
+            "swift", "sh", "html", "css", "json", "toml", "yaml", "yml", "xml", "sql", "user.js",
+            "user.css", "d.ts", "min.js", "min.css",
+        ],
+    ),
+    (
+        "Installers",
+        &["exe", "msi", "dmg", "pkg", "deb", "rpm", "apk", "appimage"],
+    ),
+    ("Fonts", &["ttf", "otf", "woff", "woff2"]),
+];
+
+/// Extensions made up of more than one part, which are file types of their own
+const COMPOUND_EXTENSIONS: &[&str] = &[
+    "tar.gz", "tar.bz2", "tar.xz", "tar.zst", "tar.lz", "tar.lz4", "tar.lzma", "tar.z", "user.js",
+    "user.css", "d.ts", "min.js", "min.css",
+];
+
+/// Get the extension of a file name, if it has one. Compound extensions such as `tar.gz` are
+/// returned whole. The extension keeps its case, see `normalise`.
+pub fn extension(filename: &OsStr) -> Option<String> {
+    if let Some(name) = filename.to_str() {
+        let bytes = name.as_bytes();
+        for compound in COMPOUND_EXTENSIONS {
+            // The name must have something before the extension, so `.tar.gz` is a `gz` file
+            let Some(dot) = bytes.len().checked_sub(compound.len() + 1) else {
+                continue;
+            };
+            if dot > 0
+                && bytes[dot] == b'.'
+                && bytes[dot + 1..].eq_ignore_ascii_case(compound.as_bytes())
+            {
+                return Some(name[dot + 1..].to_string());
+            }
+        }
+    }
+    Path::new(filename)
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(String::from)
+}
+
+/// Put a file type in lowercase, so that `JPG` and `jpg` are the same file type, unless file
+/// types are `case_sensitive`
+pub fn normalise(file_type: &str, case_sensitive: bool) -> String {
+    if case_sensitive {
+        file_type.to_string()
+    } else {
+        file_type.to_lowercase()
+    }
+}
+
+/// Where the file type of a file is taken from
+#[derive(Clone, Copy, PartialEq)]
+pub enum Detect {
+    /// The extension of the file name
+    Extension,
+    /// The contents of the file, see `content::detect`
+    Content,
+    /// The extension, unless the contents of the file are recognised and do not match it
+    Both,
+}
+
+impl Detect {
+    /// Get the file type of a file, normalised as given by `case_sensitive`. Folders do not
+    /// have a file type.
+    pub fn file_type(self, entry: &Entry, case_sensitive: bool) -> Option<String> {
+        if entry.is_dir() {
+            return None;
+        }
+        let ext = || extension(&entry.file_name()).map(|e| normalise(&e, case_sensitive));
+        let content = || entry.content_type().map(|c| c.file_type.to_string());
+        match self {
+            Detect::Extension => ext(),
+            Detect::Content => content(),
+            Detect::Both => match (ext(), entry.content_type()) {
+                (Some(ext), Some(c)) if !c.has_extension(&ext) => Some(c.file_type.to_string()),
+                (Some(ext), _) => Some(ext),
+                (None, _) => content(),
+            },
+        }
+    }
+}
+
+/// The type of a file's contents, if its extension is not one that files of that type have (e.g.
+/// a JPEG image named `photo.png`). Files without an extension, or whose type is not recognised,
+/// are not mismatched.
+pub fn mismatch(entry: &Entry) -> Option<&'static ContentType> {
+    let ext = extension(&entry.file_name())?;
+    entry.content_type().filter(|c| !c.has_extension(&ext))
+}
+
+/// Groups file types into categories, such as Images and Documents
+pub struct Categories {
+    /// The category of each file type, by lowercase file type
+    categories: HashMap<String, String>,
+}
+
+impl Default for Categories {
+    fn default() -> Categories {
+        Categories::new(&HashMap::new())
+    }
+}
+
+impl Categories {
+    /// The built-in categories, changed by `overrides` (the `[categories]` table of the config
+    /// file). Each file type that is listed is moved into the given category, which is added if
+    /// it is not a built-in one. A category without any file types is removed, so that its
+    /// files are in `Other` instead.
+    pub fn new(overrides: &HashMap<String, Vec<String>>) -> Categories {
+        let mut categories = HashMap::new();
+        for (category, file_types) in CATEGORIES {
+            for file_type in *file_types {
+                categories.insert(file_type.to_string(), category.to_string());
+            }
+        }
+
+        for (category, file_types) in overrides {
+            if file_types.is_empty() {
+                categories.retain(|_, c| c != category);
+            }
+            for file_type in file_types {
+                let file_type = file_type.trim_start_matches('.').to_lowercase();
+                categories.insert(file_type, category.to_string());
+            }
+        }
+        Categories { categories }
+    }
+
+    /// The category of a file type, or `Other` if it is not in any category. File types are not
+    /// case-sensitive.
+    pub fn category(&self, file_type: &str) -> &str {
+        match self.categories.get(&file_type.to_lowercase()) {
+            Some(c) => c,
+            None => OTHER,
+        }
+    }
+}
+
+/// The folders that files are sorted into when sorting by file type
+#[derive(Clone, Copy, PartialEq)]
+pub enum By {
+    /// A folder for each file type, e.g. `jpg`
+    Extension,
+    /// A folder for each category, e.g. `Images`
+    Category,
+    /// A folder for each file type inside the folder for its category, e.g. `Images/jpg`
+    CategoryExtension,
+}
+
+impl By {
+    /// The folder for files of the given file type, relative to the folder being sorted
+    pub fn folder(self, file_type: &str, categories: &Categories) -> PathBuf {
+        match self {
+            By::Extension => PathBuf::from(file_type),
+            By::Category => PathBuf::from(categories.category(file_type)),
+            By::CategoryExtension => PathBuf::from(categories.category(file_type)).join(file_type),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extensions() {
+        let cases = [
+            ("photo.jpg", Some("jpg")),
+            ("backup.tar.gz", Some("tar.gz")),
+            ("A.TAR.GZ", Some("TAR.GZ")),
+            ("x.user.js", Some("user.js")),
+            ("types.d.ts", Some("d.ts")),
+            // Nothing before the compound extension
+            (".tar.gz", Some("gz")),
+            ("tar.gz", Some("gz")),
+            ("archive.old.gz", Some("gz")),
+            ("user.js", Some("js")),
+            ("résumé.pdf", Some("pdf")),
+            ("日本語.tar.gz", Some("tar.gz")),
+            ("€.d.ts", Some("d.ts")),
+            ("naïveuser.js", Some("js")),
+            ("Makefile", None),
+            (".bashrc", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(extension(OsStr::new(name)).as_deref(), expected, "{name}");
+        }
+    }
+}
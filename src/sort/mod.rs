@@ -1,2 +1,6 @@
+pub mod content;
 pub mod date;
 pub mod file_type;
+pub mod name;
+pub mod rules;
+pub mod size;
@@ -0,0 +1,227 @@
+use std::path::PathBuf;
+
+use regex::Regex;
+
+/// A destination folder made from the groups a regex captures in each file name, such as `{1}`
+/// or `{client}/{year}`
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+enum Part {
+    Text(String),
+    Index(usize),
+    Name(String),
+}
+
+impl Template {
+    /// Parse a template. Groups are referred to by number (`{1}`, with `{0}` being the whole
+    /// match) or by name (`{client}` for `(?<client>...)`). `{{` and `}}` are a literal `{` and
+    /// `}`, and `/` separates nested folders.
+    pub fn parse(template: &str) -> Result<Template, String> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut group = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        group.push(c);
+                    }
+                    if !closed {
+                        return Err("Unmatched '{'. Use '{{' for a literal '{'".to_string());
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(group_part(&group)?);
+                }
+                '}' => return Err("Unmatched '}'. Use '}}' for a literal '}'".to_string()),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        if !parts.iter().any(|p| !matches!(p, Part::Text(_))) {
+            return Err("There are no groups in it, e.g. {1} or {client}".to_string());
+        }
+        Ok(Template { parts })
+    }
+
+    /// Check that every group the template refers to is in the regex
+    pub fn check(&self, re: &Regex) -> Result<(), String> {
+        for part in &self.parts {
+            match part {
+                Part::Index(i) if *i >= re.captures_len() => {
+                    return Err(format!("The pattern '{re}' has no group {{{i}}}"));
+                }
+                Part::Name(name) if !re.capture_names().any(|n| n == Some(name)) => {
+                    return Err(format!("The pattern '{re}' has no group named '{name}'"));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The folder for a file, relative to the folder being sorted, or `None` if the regex does
+    /// not match the file name. Groups that did not capture anything are left empty, and so are
+    /// the folders made only of them. `None` is also returned if no folder is left, or if a
+    /// folder would be `.` or `..`.
+    pub fn folder(&self, re: &Regex, filename: &str) -> Option<PathBuf> {
+        let captures = re.captures(filename)?;
+        let mut folder = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => folder.push_str(text),
+                Part::Index(i) => folder.push_str(captures.get(*i).map_or("", |m| m.as_str())),
+                Part::Name(name) => folder.push_str(captures.name(name).map_or("", |m| m.as_str())),
+            }
+        }
+
+        let names: Vec<&str> = folder.split('/').filter(|n| !n.is_empty()).collect();
+        if names.is_empty() || names.iter().any(|n| *n == "." || *n == "..") {
+            return None;
+        }
+        Some(names.iter().collect())
+    }
+}
+
+/// A `{...}` group, by number or name
+fn group_part(group: &str) -> Result<Part, String> {
+    if let Ok(i) = group.parse::<usize>() {
+        return Ok(Part::Index(i));
+    }
+    let is_name = group.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && group.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_name {
+        return Err(format!(
+            "'{{{group}}}' is not a group. Use a number such as {{1}} or a name such as {{client}}"
+        ));
+    }
+    Ok(Part::Name(group.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(template: &str, re: &str, filename: &str) -> Option<PathBuf> {
+        let template = Template::parse(template).unwrap();
+        let re = Regex::new(re).unwrap();
+        template.check(&re).unwrap();
+        template.folder(&re, filename)
+    }
+
+    #[test]
+    fn numbered_and_named_groups() {
+        let re = r"^(?<client>[a-z]+)_(\d{4})";
+        assert_eq!(
+            folder("{client}/{2}", re, "acme_2024_invoice.pdf"),
+            Some(PathBuf::from("acme").join("2024"))
+        );
+        assert_eq!(
+            folder("{0}", re, "acme_2024_invoice.pdf"),
+            Some(PathBuf::from("acme_2024"))
+        );
+        assert_eq!(
+            folder("Client {1} ({2})", re, "acme_2024.pdf"),
+            Some(PathBuf::from("Client acme (2024)"))
+        );
+        assert_eq!(folder("{1}", re, "2024_acme.pdf"), None);
+    }
+
+    #[test]
+    fn escaped_braces() {
+        assert_eq!(
+            folder("{{{1}}}", r"^(\w+)\.", "report.pdf"),
+            Some(PathBuf::from("{report}"))
+        );
+        assert_eq!(
+            folder("a}}b{{c {1}", r"^(\w+)\.", "report.pdf"),
+            Some(PathBuf::from("a}b{c report"))
+        );
+    }
+
+    #[test]
+    fn invalid_templates() {
+        let cases = [
+            ("{1", "Unmatched '{'. Use '{{' for a literal '{'"),
+            ("{1}}", "Unmatched '}'. Use '}}' for a literal '}'"),
+            ("1}", "Unmatched '}'. Use '}}' for a literal '}'"),
+            ("{{1}}", "There are no groups in it, e.g. {1} or {client}"),
+            ("folder", "There are no groups in it, e.g. {1} or {client}"),
+            (
+                "{}",
+                "'{}' is not a group. Use a number such as {1} or a name such as {client}",
+            ),
+            (
+                "{1a}",
+                "'{1a}' is not a group. Use a number such as {1} or a name such as {client}",
+            ),
+            (
+                "{a-b}",
+                "'{a-b}' is not a group. Use a number such as {1} or a name such as {client}",
+            ),
+        ];
+        for (template, error) in cases {
+            match Template::parse(template) {
+                Ok(_) => panic!("'{template}' should not parse"),
+                Err(e) => assert_eq!(e, error, "{template}"),
+            }
+        }
+    }
+
+    #[test]
+    fn groups_must_be_in_the_regex() {
+        let re = Regex::new(r"^(?<client>[a-z]+)_(\d+)").unwrap();
+        assert!(Template::parse("{2}").unwrap().check(&re).is_ok());
+        assert!(Template::parse("{_x}").unwrap().check(&re).is_err());
+        assert!(Template::parse("{3}").unwrap().check(&re).is_err());
+        assert!(Template::parse("{year}").unwrap().check(&re).is_err());
+    }
+
+    #[test]
+    fn empty_folders_are_dropped() {
+        let re = r"^(?:(\w+)-)?(\w*)\.";
+        // The first group does not take part in the match
+        assert_eq!(
+            folder("{1}/{2}", re, "report.pdf"),
+            Some(PathBuf::from("report"))
+        );
+        assert_eq!(
+            folder("{1}//x/{2}/", re, "a-b.pdf"),
+            Some(PathBuf::from("a").join("x").join("b"))
+        );
+        // No folder is left
+        assert_eq!(folder("{1}/{2}", re, ".pdf"), None);
+    }
+
+    #[test]
+    fn dot_folders_are_rejected() {
+        let re = r"^([^_]*)_";
+        assert_eq!(folder("{1}", re, "._x"), None);
+        assert_eq!(folder("{1}", re, ".._x"), None);
+        assert_eq!(folder("a/{1}", re, ".._x"), None);
+        assert_eq!(folder("{1}", re, "..._x"), Some(PathBuf::from("...")));
+        assert_eq!(
+            folder("{1}", re, ".hidden_x"),
+            Some(PathBuf::from(".hidden"))
+        );
+    }
+}
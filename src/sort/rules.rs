@@ -0,0 +1,155 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::filter::{All, DateFilter, Filter, NameFilter, Pattern, SizeFilter, Syntax, TypeFilter};
+use crate::sort::date::parse_date;
+use crate::sort::size::{parse_size, KB};
+use crate::util::config::expand_home;
+use crate::util::walk::Entry;
+
+/// A rules file, made up of an ordered list of `[[rule]]` tables
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RuleDef>,
+}
+
+/// A rule as it is written in the rules file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleDef {
+    name: Option<String>,
+    destination: String,
+    includes: Option<String>,
+    excludes: Option<String>,
+    #[serde(default)]
+    regex: bool,
+    #[serde(default)]
+    glob: bool,
+    #[serde(default, rename = "ignore-case")]
+    ignore_case: bool,
+    before: Option<toml::Value>,
+    after: Option<toml::Value>,
+    min: Option<toml::Value>,
+    max: Option<toml::Value>,
+    #[serde(rename = "type")]
+    file_type: Option<String>,
+}
+
+/// A set of criteria paired with the folder that matching files are moved into.
+///
+/// All of the criteria given must match. A rule without any criteria matches every file.
+pub struct Rule {
+    pub name: String,
+    /// Destination folder, relative to the directory being sorted unless absolute
+    pub destination: PathBuf,
+    pub criteria: All,
+}
+
+impl Rule {
+    fn from_def(idx: usize, def: RuleDef) -> Result<Rule, String> {
+        let name = def.name.unwrap_or_else(|| format!("#{}", idx + 1));
+
+        let syntax = if def.regex {
+            Syntax::Regex
+        } else if def.glob {
+            Syntax::Glob
+        } else {
+            Syntax::Text
+        };
+        let pattern = |p: Option<String>| -> Result<Option<Pattern>, String> {
+            match p {
+                Some(p) => Pattern::new(&p, syntax, def.ignore_case)
+                    .map(Some)
+                    .map_err(|error| format!("Rule '{name}' has an invalid regex:\n{error}")),
+                None => Ok(None),
+            }
+        };
+        let date = |d: Option<toml::Value>| -> Result<Option<i64>, String> {
+            let d = match d {
+                Some(toml::Value::String(s)) => s,
+                Some(toml::Value::Datetime(dt)) => dt.to_string(),
+                Some(v) => return Err(format!("Rule '{name}' has an invalid date: {v}")),
+                None => return Ok(None),
+            };
+            match parse_date(&d) {
+                Ok(ts) => Ok(Some(ts)),
+                Err(error) => Err(format!("Rule '{name}' has an invalid date '{d}'. {error}")),
+            }
+        };
+
+        // Sizes without a unit are in KB, like on the command line
+        let size = |s: Option<toml::Value>| -> Result<Option<u64>, String> {
+            let s = match s {
+                Some(toml::Value::String(s)) => s,
+                Some(toml::Value::Integer(n)) if n >= 0 => n.to_string(),
+                Some(toml::Value::Float(n)) if n >= 0.0 => n.to_string(),
+                Some(v) => return Err(format!("Rule '{name}' has an invalid size: {v}")),
+                None => return Ok(None),
+            };
+            match parse_size(&s, KB) {
+                Ok(bytes) => Ok(Some(bytes)),
+                Err(error) => Err(format!("Rule '{name}' has an invalid size '{s}': {error}")),
+            }
+        };
+
+        let mut criteria = All::new()
+            .with(NameFilter {
+                includes: pattern(def.includes)?,
+                excludes: pattern(def.excludes)?,
+            })
+            .with(DateFilter {
+                before: date(def.before)?,
+                after: date(def.after)?,
+            })
+            .with(SizeFilter {
+                min: size(def.min)?,
+                max: size(def.max)?,
+            });
+        if let Some(file_type) = def.file_type {
+            criteria.push(TypeFilter::new(&file_type));
+        }
+
+        Ok(Rule {
+            destination: expand_home(&def.destination),
+            name,
+            criteria,
+        })
+    }
+}
+
+impl Filter for Rule {
+    /// Check whether a file or folder matches all of the criteria of this rule
+    fn matches(&self, entry: &Entry) -> bool {
+        self.criteria.matches(entry)
+    }
+}
+
+/// Read the rules from a rules file, keeping the order they were written in
+pub fn load(path: &Path) -> Result<Vec<Rule>, String> {
+    let text = fs::read_to_string(path).map_err(|error| {
+        format!(
+            "Unable to read the rules file \"{}\": {}",
+            path.display(),
+            error
+        )
+    })?;
+    let file: RulesFile = toml::from_str(&text)
+        .map_err(|error| format!("Invalid rules file \"{}\":\n{}", path.display(), error))?;
+
+    if file.rule.is_empty() {
+        return Err(format!(
+            "The rules file \"{}\" does not contain any rules",
+            path.display()
+        ));
+    }
+
+    file.rule
+        .into_iter()
+        .enumerate()
+        .map(|(idx, def)| Rule::from_def(idx, def))
+        .collect()
+}
@@ -0,0 +1,252 @@
+/// The number of bytes in a kilobyte, the unit used for sizes given without one on the command
+/// line
+pub const KB: u64 = 1000;
+
+/// Parse a size such as `500`, `2MB` or `1.5 GiB` into a number of bytes. A size without a unit
+/// is in `default_unit` (in bytes, e.g. `1` or `KB`).
+///
+/// Decimal units (`KB`, `MB`, `GB`, `TB`) are powers of 1000 and binary units (`KiB`, `MiB`,
+/// `GiB`, `TiB`) are powers of 1024. Units are not case-sensitive.
+pub fn parse_size(size: &str, default_unit: u64) -> Result<u64, String> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let unit = unit.trim();
+    if number.is_empty() {
+        return Err(format!("'{size}' is not a valid size"));
+    }
+
+    let multiplier = match unit.to_lowercase().as_str() {
+        "" => default_unit,
+        "b" => 1,
+        "k" | "kb" => KB,
+        "m" | "mb" => 1000u64.pow(2),
+        "g" | "gb" => 1000u64.pow(3),
+        "t" | "tb" => 1000u64.pow(4),
+        "kib" => 1024,
+        "mib" => 1024u64.pow(2),
+        "gib" => 1024u64.pow(3),
+        "tib" => 1024u64.pow(4),
+        _ => {
+            return Err(format!(
+                "Unknown size unit '{unit}'. Use B, KB, KiB, MB, MiB, GB, GiB, TB or TiB"
+            ))
+        }
+    };
+
+    let too_large = || format!("The size '{size}' is too large");
+    if let Ok(n) = number.parse::<u64>() {
+        return n.checked_mul(multiplier).ok_or_else(too_large);
+    }
+    let n: f64 = match number.parse() {
+        Ok(n) => n,
+        Err(_) => return Err(format!("'{size}' is not a valid size")),
+    };
+    let bytes = (n * multiplier as f64).round();
+    if bytes >= u64::MAX as f64 {
+        return Err(too_large());
+    }
+    Ok(bytes as u64)
+}
+
+/// Size ranges that files are sorted into, split at the given sizes. Each range includes its
+/// lower bound but not its upper bound.
+pub struct Buckets {
+    /// The sizes the ranges are split at, in bytes, from smallest to largest
+    pub bounds: Vec<u64>,
+    /// The folder name for each range, one more than the number of bounds
+    pub labels: Vec<String>,
+}
+
+impl Buckets {
+    /// Parse a list of sizes such as `1MB,100MB,1GB`. A size without a unit is in
+    /// `default_unit`.
+    ///
+    /// The ranges are labelled by the sizes as they are written, e.g. `<1MB`, `1MB-100MB`,
+    /// `100MB-1GB` and `>=1GB`.
+    pub fn parse(buckets: &str, default_unit: u64) -> Result<Buckets, String> {
+        let mut bounds = vec![];
+        let mut names = vec![];
+        for size in buckets.split(',') {
+            let bytes = parse_size(size, default_unit)
+                .map_err(|error| format!("Invalid size '{}': {error}", size.trim()))?;
+            if bounds.last().is_some_and(|&last| bytes <= last) {
+                return Err(format!(
+                    "The sizes must go from smallest to largest, but '{}' is not larger than the size before it",
+                    size.trim()
+                ));
+            }
+            bounds.push(bytes);
+
+            let mut name: String = size.split_whitespace().collect();
+            if name.ends_with(|c: char| c.is_ascii_digit()) && default_unit == KB {
+                name.push_str("KB");
+            }
+            names.push(name);
+        }
+
+        // `<` and `>` cannot be used in file names on Windows
+        let (under, over) = if cfg!(windows) {
+            ("Under ", "Over ")
+        } else {
+            ("<", ">=")
+        };
+        let mut labels = vec![format!("{under}{}", names[0])];
+        labels.extend(names.windows(2).map(|w| format!("{}-{}", w[0], w[1])));
+        labels.push(format!("{over}{}", names[names.len() - 1]));
+
+        Ok(Buckets { bounds, labels })
+    }
+
+    /// Use other folder names for the ranges, from smallest to largest
+    pub fn set_labels(&mut self, labels: Vec<String>) -> Result<(), String> {
+        if labels.len() != self.labels.len() {
+            return Err(format!(
+                "{} sizes make {} ranges, but {} labels were given",
+                self.bounds.len(),
+                self.labels.len(),
+                labels.len()
+            ));
+        }
+        if let Some(label) = labels
+            .iter()
+            .find(|l| l.is_empty() || l.contains(['/', '\\']) || *l == "." || *l == "..")
+        {
+            return Err(format!("'{label}' cannot be used as a folder name"));
+        }
+        self.labels = labels;
+        Ok(())
+    }
+
+    /// The folder name for files of the given size in bytes
+    pub fn label(&self, size: u64) -> &str {
+        let idx = self.bounds.partition_point(|&bound| bound <= size);
+        &self.labels[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units() {
+        let cases = [
+            ("5B", 5),
+            ("5 b", 5),
+            ("2k", 2_000),
+            ("2KB", 2_000),
+            ("2MB", 2_000_000),
+            ("2 GB", 2_000_000_000),
+            ("2tb", 2_000_000_000_000),
+            ("2KiB", 2 * 1024),
+            ("2MiB", 2 * 1024 * 1024),
+            ("2 gib", 2 * 1024 * 1024 * 1024),
+            ("2TiB", 2 * 1024u64.pow(4)),
+        ];
+        for (size, bytes) in cases {
+            assert_eq!(parse_size(size, KB), Ok(bytes), "{size}");
+        }
+    }
+
+    #[test]
+    fn default_unit() {
+        assert_eq!(parse_size("500", KB), Ok(500_000));
+        assert_eq!(parse_size(" 500 ", 1), Ok(500));
+        assert_eq!(parse_size("1.5", KB), Ok(1_500));
+    }
+
+    #[test]
+    fn fractions_are_rounded() {
+        assert_eq!(parse_size("1.5MB", KB), Ok(1_500_000));
+        assert_eq!(parse_size("1.25KiB", KB), Ok(1_280));
+        assert_eq!(parse_size("0.1KB", KB), Ok(100));
+        assert_eq!(parse_size("2.5", 1), Ok(3));
+        assert_eq!(parse_size("2.4", 1), Ok(2));
+        assert_eq!(parse_size(".5KB", KB), Ok(500));
+    }
+
+    #[test]
+    fn too_large() {
+        assert_eq!(parse_size("18446744073709551615", 1), Ok(u64::MAX));
+        assert!(parse_size("18446744073709551616", 1).is_err());
+        assert!(parse_size("20000000TB", KB).is_err());
+        assert!(parse_size("18446744073709551615KB", KB).is_err());
+        assert!(parse_size("20000000.5TB", KB).is_err());
+        assert_eq!(
+            parse_size("17000000TB", KB),
+            Ok(17_000_000 * 1000u64.pow(4))
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        for size in ["", "MB", "1.2.3MB", "-1MB", "5 XB", "5 MBs"] {
+            assert!(parse_size(size, KB).is_err(), "{size}");
+        }
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn labels() {
+        let buckets = Buckets::parse("1MB, 100 MB,1GB", KB).unwrap();
+        assert_eq!(buckets.bounds, [1_000_000, 100_000_000, 1_000_000_000]);
+        assert_eq!(buckets.labels, ["<1MB", "1MB-100MB", "100MB-1GB", ">=1GB"]);
+
+        // Sizes without a unit are labelled with the default unit
+        let buckets = Buckets::parse("500,2000", KB).unwrap();
+        assert_eq!(buckets.bounds, [500_000, 2_000_000]);
+        assert_eq!(buckets.labels, ["<500KB", "500KB-2000KB", ">=2000KB"]);
+    }
+
+    #[test]
+    fn sizes_must_increase() {
+        assert!(Buckets::parse("1MB,1000KB", KB).is_err());
+        assert!(Buckets::parse("1MB,500KB", KB).is_err());
+        assert!(Buckets::parse("1MB,,2MB", KB).is_err());
+    }
+
+    #[test]
+    fn label_at_bounds() {
+        let mut buckets = Buckets::parse("1MB,100MB,1GB", KB).unwrap();
+        buckets
+            .set_labels(vec![
+                "small".into(),
+                "medium".into(),
+                "large".into(),
+                "huge".into(),
+            ])
+            .unwrap();
+
+        let cases = [
+            (0, "small"),
+            (999_999, "small"),
+            (1_000_000, "medium"),
+            (99_999_999, "medium"),
+            (100_000_000, "large"),
+            (999_999_999, "large"),
+            (1_000_000_000, "huge"),
+            (u64::MAX, "huge"),
+        ];
+        for (size, label) in cases {
+            assert_eq!(buckets.label(size), label, "{size}");
+        }
+    }
+
+    #[test]
+    fn set_labels() {
+        let mut buckets = Buckets::parse("1MB", KB).unwrap();
+        assert!(buckets.set_labels(vec!["small".into()]).is_err());
+        assert!(buckets
+            .set_labels(vec!["small".into(), "a/b".into()])
+            .is_err());
+        assert!(buckets.set_labels(vec!["..".into(), "big".into()]).is_err());
+        assert!(buckets.set_labels(vec!["".into(), "big".into()]).is_err());
+        assert!(buckets
+            .set_labels(vec!["small".into(), "big".into()])
+            .is_ok());
+        assert_eq!(buckets.labels, ["small", "big"]);
+    }
+}
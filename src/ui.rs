@@ -0,0 +1,256 @@
+use std::io::{self, stdin, stdout, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::Instant;
+
+use cabinet::execute::{Outcome, Report};
+use cabinet::util::action::Action;
+use cabinet::util::config::Config;
+use cabinet::util::path::template;
+use cabinet::util::walk::{self, Entry};
+use cabinet::Plan;
+use clap::ArgMatches;
+
+use crate::context::Context;
+
+/// Exit code used when a prompt is needed but `--non-interactive` was given
+pub const EXIT_PROMPT_REQUIRED: i32 = 3;
+
+/// Get the current path where the files will be sorted into. This will also print the current path.
+pub fn get_current_path(path: Option<PathBuf>) -> PathBuf {
+    let paths_parent = path.as_ref().unwrap().display().to_string();
+    println!("CURRENT PATH: {}", &paths_parent);
+    path.unwrap()
+}
+
+/// Get filepath object. Only called when it is a valid path or template path.
+///
+/// Templates defined in the config file take precedence over the built-in templates.
+pub fn get_path(path: &String, use_template: bool, config: &Config) -> Option<PathBuf> {
+    let mut path_ref: Option<PathBuf> = None;
+
+    if use_template {
+        path_ref = template(path, config);
+        if path_ref.is_none() {
+            println!("The template '{}' does not exist.", &path.to_lowercase());
+        }
+    } else if Path::new(path).exists() {
+        path_ref = Some(PathBuf::from(path));
+    } else {
+        println!("Directory \"{}\" either does not exist or this program is missing permissions to access it.", &path);
+    }
+
+    path_ref
+}
+
+/// Read the entries of the directory to sort, printing a warning for anything that is skipped
+pub fn walk(root: &Path, skip: &[PathBuf], ctx: &Context) -> Vec<Entry> {
+    let walk = walk::walk(root, &ctx.walk, skip);
+    for warning in &walk.warnings {
+        println!("WARNING: {warning}");
+    }
+    walk.entries
+}
+
+/// Create the folder to store the sorted files in. If folder name already exists,
+/// ask user if they wish to proceed or not.
+///
+/// If `auto_yes` is `true` (or `--yes` was given), then a folder will be used even if it already
+/// exists. With `--non-interactive`, the program exits with `EXIT_PROMPT_REQUIRED` instead of
+/// asking.
+///
+/// In a dry run, nothing is created and the user is not asked anything.
+pub fn create_folder(
+    path: PathBuf,
+    folder: String,
+    auto_yes: bool,
+    ctx: &mut Context,
+) -> Result<PathBuf, io::Error> {
+    let mut path = path;
+    path.push(&folder);
+
+    if Path::new(&path).exists() {
+        if ctx.dry_run() {
+            println!(
+                "The folder '{}' already exists and would be used\n --> \"{}\"",
+                &folder,
+                &path.display()
+            );
+            return Ok(path);
+        }
+        if auto_yes || ctx.assume_yes {
+            return Ok(path);
+        }
+        if ctx.non_interactive {
+            println!("ERROR: The folder with the name '{folder}' already exists. Use --yes to sort files into it anyway");
+            exit(EXIT_PROMPT_REQUIRED);
+        }
+
+        print!("The folder with the name '{folder}' already exists. Sorted files will be placed in this folder anyway. Proceed? [y/N] ");
+        let _ = stdout().flush();
+        let mut ans = String::new();
+        stdin().read_line(&mut ans).expect("Malformed input");
+
+        // could also use .trim_end_matches('\n').trim_end_matches('\r') // ORDER IS IMPORTANT
+        if ans.trim_end() == "y" {
+            println!("\nContinuing anyway...");
+            return Ok(path);
+        } else {
+            println!("\nAborted.");
+            exit(0);
+        }
+    }
+
+    if ctx.dry_run() {
+        println!(
+            "New folder '{}' would be created\n --> \"{}\"",
+            &folder,
+            &path.display()
+        );
+        ctx.announced.insert(path.clone());
+        return Ok(path);
+    }
+
+    let f = ctx.executor.create_folder(&path);
+    match f {
+        Ok(_) => {
+            println!(
+                "New folder '{}' has been created\n --> \"{}\"",
+                &folder,
+                &path.display()
+            );
+            Ok(path)
+        }
+        Err(error) => {
+            println!(
+                "There was a problem creating the folder for \"{}\":\n{:?}",
+                &folder, error
+            );
+            Err(error)
+        }
+    }
+}
+
+/// Get the folder that files are sorted into: the `--output` folder inside `parent` if one is
+/// given (which is created with `create_folder`), otherwise `parent` itself. Returns `None` if
+/// the output folder could not be created.
+pub fn output_root(args: &ArgMatches, parent: PathBuf, ctx: &mut Context) -> Option<PathBuf> {
+    match args.get_one::<String>("output") {
+        Some(out_name) if !out_name.is_empty() => {
+            create_folder(parent, out_name.to_string(), false, ctx).ok()
+        }
+        _ => Some(parent),
+    }
+}
+
+/// Create the folders of the plan that do not exist yet, along with their parent folders. Each
+/// one is printed in the same way as by `create_folder`.
+fn create_plan_folders(plan: &Plan, ctx: &mut Context) {
+    for folder in &plan.folders {
+        if folder.exists() || ctx.announced.contains(folder) {
+            continue;
+        }
+        let name = folder.file_name().unwrap_or_default().to_string_lossy();
+
+        if ctx.dry_run() {
+            ctx.announced.insert(folder.clone());
+            println!(
+                "New folder '{}' would be created\n --> \"{}\"",
+                name,
+                folder.display()
+            );
+            continue;
+        }
+        match ctx.executor.create_folder_all(folder) {
+            Ok(_) => println!(
+                "New folder '{}' has been created\n --> \"{}\"",
+                name,
+                folder.display()
+            ),
+            // Sorting the files into the folder fails as well, and is reported then
+            Err(error) => println!(
+                "There was a problem creating the folder for \"{}\":\n{:?}",
+                name, error
+            ),
+        }
+    }
+}
+
+/// Print the summary shown once all files have been processed
+pub fn print_summary(report: &Report, total: usize, start: Instant, ctx: &Context) {
+    if ctx.dry_run() {
+        println!(
+            "Dry run: {}/{} files would be sorted into folders. Nothing was changed",
+            report.sorted, &total
+        );
+        print_conflicts(report);
+        return;
+    }
+
+    let duration = start.elapsed();
+    stdout().flush().unwrap();
+
+    print!("\rProcessed 100%   \n");
+    println!("Time taken: {:?}", duration);
+    println!("Sorted {}/{} files into folders", report.sorted, &total);
+    print_conflicts(report);
+    for warning in &report.warnings {
+        println!("WARNING: {warning}");
+    }
+}
+
+fn print_conflicts(report: &Report) {
+    if report.skipped > 0 {
+        println!(
+            "Skipped {} files as a file with the same name already exists in the destination",
+            report.skipped
+        );
+    }
+    if report.duplicates > 0 {
+        println!(
+            "Removed {} files that were identical to a file already in the destination",
+            report.duplicates
+        );
+    }
+}
+
+/// Sort the files into the folders given by the plan, printing the progress and a summary. In a
+/// dry run, every planned change is printed instead.
+pub fn run_plan(plan: &Plan, total: usize, ctx: &mut Context) {
+    let start = Instant::now();
+    let dry_run = ctx.dry_run();
+    let action = ctx.executor.options.action;
+    create_plan_folders(plan, ctx);
+
+    let report = ctx.executor.execute(plan, |idx, step, outcome| {
+        let source = step.source.display();
+        match outcome {
+            Ok(Outcome::Sorted(dest)) if dry_run => match action {
+                Action::Move => println!("{} -> {}", source, dest.display()),
+                a => println!("{} -> {} ({})", source, dest.display(), a.name()),
+            },
+            Ok(Outcome::Skipped) if dry_run => println!("{} (skipped, already exists)", source),
+            Ok(Outcome::Duplicate(dest)) if dry_run => println!(
+                "{} (duplicate of {}, would be removed)",
+                source,
+                dest.display()
+            ),
+            Ok(_) => {}
+            Err(error) => println!("There was a problem opening the file:\n{:?}", error),
+        }
+
+        // Nothing is printed in a dry run, as it would get in the way of the list of changes
+        if !dry_run {
+            let done = idx as f64 / plan.len() as f64;
+            print!("\rProcessing {:.1}%", done * 100.0);
+            let _ = stdout().flush();
+        }
+    });
+
+    print_summary(&report, total, start, ctx);
+}
+
+// TODO: Consider adding a progress bar
+pub fn sort_files(path: &Path, files: &[Entry], ctx: &mut Context) {
+    run_plan(&Plan::into_folder(files, path), files.len(), ctx);
+}
@@ -0,0 +1,97 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::util::{copy, moved_link_target, path, rename};
+
+/// What is done with each file that is sorted into a folder
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Move,
+    /// Copy the file, leaving the original where it is
+    Copy,
+    /// Create a hard link to the file
+    Hardlink,
+    /// Create a symbolic link to the file
+    Symlink,
+    /// Create a copy that shares its data with the original, on file systems that support it
+    Reflink,
+}
+
+impl Action {
+    /// The name of the action, as it is given on the command line and stored in the journal
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Move => "move",
+            Action::Copy => "copy",
+            Action::Hardlink => "hardlink",
+            Action::Symlink => "symlink",
+            Action::Reflink => "reflink",
+        }
+    }
+
+    /// Move, copy or link the file or folder at `source` to `dest`
+    pub fn perform(&self, source: &Path, dest: &Path, verify: bool) -> Result<(), io::Error> {
+        match self {
+            Action::Move => rename(source, dest, verify),
+            Action::Copy => copy::copy(source, dest, verify, false),
+            Action::Reflink => copy::copy(source, dest, verify, true).map_err(reflink_error),
+            Action::Hardlink => {
+                if fs::symlink_metadata(source)?.is_dir() {
+                    return Err(io::Error::other(format!(
+                        "\"{}\" is a folder, which cannot be hard linked",
+                        source.display()
+                    )));
+                }
+                // A hard link to a symbolic link keeps its target, so a link with a relative
+                // target is recreated to point to the same place from its new folder instead
+                match moved_link_target(source, dest) {
+                    Some(target) => link(dest, |part| symlink(&target, part)),
+                    None => link(dest, |part| fs::hard_link(source, part)),
+                }
+            }
+            // An absolute target keeps the link working wherever the destination folder is
+            Action::Symlink => link(dest, |part| symlink(&path::normalize(source), part)),
+        }
+    }
+}
+
+/// Create a link next to `dest` under a temporary name and rename it to `dest`, so that a file
+/// already at `dest` is replaced by the link (as when copying) rather than causing an error
+fn link(dest: &Path, create: impl FnOnce(&Path) -> Result<(), io::Error>) -> Result<(), io::Error> {
+    let part = copy::part_path(dest);
+    // Left behind by an earlier run that was interrupted
+    let _ = fs::remove_file(&part);
+
+    create(&part)?;
+    let result = fs::rename(&part, dest);
+    if result.is_err() {
+        let _ = fs::remove_file(&part);
+    }
+    result
+}
+
+/// Explain a failed reflink on a file system that does not support them
+fn reflink_error(error: io::Error) -> io::Error {
+    match error.kind() {
+        io::ErrorKind::Unsupported | io::ErrorKind::CrossesDevices => io::Error::new(
+            error.kind(),
+            "The file system does not support reflinks here. Use '--action copy' instead",
+        ),
+        _ => error,
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> Result<(), io::Error> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> Result<(), io::Error> {
+    if target.is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
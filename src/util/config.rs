@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// User configuration, read from `~/.config/cabinet/config.toml` by default.
+///
+/// ```toml
+/// [templates]
+/// scans = "~/Scanner/Inbox"
+///
+/// [alias]
+/// tidy = "type -t downloads"
+///
+/// [defaults.name]
+/// regex = true
+///
+/// [categories]
+/// Ebooks = ["epub", "mobi"]
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Named template paths, which take precedence over the built-in templates
+    pub templates: HashMap<String, String>,
+    /// Command aliases, expanded before the command line is parsed
+    pub alias: HashMap<String, Alias>,
+    /// Default options for each command, keyed by the long name of the option
+    pub defaults: HashMap<String, toml::Table>,
+    /// File types to add to (or move between) the categories used by `cab type --by category`
+    pub categories: HashMap<String, Vec<String>>,
+}
+
+/// An alias can be written either as a single string or as a list of arguments
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Alias {
+    Line(String),
+    Args(Vec<String>),
+}
+
+impl Alias {
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            Alias::Line(line) => line.split_whitespace().map(String::from).collect(),
+            Alias::Args(args) => args.clone(),
+        }
+    }
+}
+
+impl Config {
+    /// Get the default location of the config file
+    pub fn default_path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("cabinet").join("config.toml"))
+    }
+
+    /// Load the config file at `path`, or the default config file if no path is given. A missing
+    /// default config file is not an error.
+    pub fn load(path: Option<&Path>) -> Result<Config, String> {
+        let file = match path {
+            Some(p) => p.to_path_buf(),
+            None => match Config::default_path() {
+                Some(p) if p.exists() => p,
+                _ => return Ok(Config::default()),
+            },
+        };
+
+        let text = fs::read_to_string(&file).map_err(|error| {
+            format!(
+                "Unable to read the config file \"{}\": {}",
+                file.display(),
+                error
+            )
+        })?;
+        toml::from_str(&text)
+            .map_err(|error| format!("Invalid config file \"{}\":\n{}", file.display(), error))
+    }
+
+    /// Get the path of a user-defined template. Template names are case-insensitive.
+    pub fn template(&self, name: &str) -> Option<PathBuf> {
+        let (_, path) = self
+            .templates
+            .iter()
+            .find(|(k, _)| k.to_lowercase() == name.to_lowercase())?;
+        Some(expand_home(path))
+    }
+
+    /// Get the default options for a command as command line arguments, leaving out the options
+    /// for which `given` returns `true`
+    pub fn default_args(
+        &self,
+        command: &str,
+        given: impl Fn(&str) -> bool,
+    ) -> Result<Vec<String>, String> {
+        let mut args: Vec<String> = vec![];
+        let table = match self.defaults.get(command) {
+            Some(t) => t,
+            None => return Ok(args),
+        };
+
+        for (key, value) in table {
+            if given(key) {
+                continue;
+            }
+            let flag = format!("--{key}");
+            match value {
+                toml::Value::Boolean(true) => args.push(flag),
+                toml::Value::Boolean(false) => {}
+                toml::Value::Array(values) => {
+                    for v in values {
+                        args.push(flag.clone());
+                        args.push(value_to_arg(key, v)?);
+                    }
+                }
+                v => {
+                    args.push(flag);
+                    args.push(value_to_arg(key, v)?);
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+fn value_to_arg(key: &str, value: &toml::Value) -> Result<String, String> {
+    match value {
+        toml::Value::String(s) => Ok(s.to_string()),
+        toml::Value::Integer(i) => Ok(i.to_string()),
+        toml::Value::Float(f) => Ok(f.to_string()),
+        toml::Value::Datetime(d) => Ok(d.to_string()),
+        _ => Err(format!("Unsupported value for the default option '{key}'")),
+    }
+}
+
+/// Replace a leading `~` with the user's home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    if path == "~" {
+        if let Some(home) = dirs::home_dir() {
+            return home;
+        }
+    }
+    if let Some(rest) = path.strip_prefix("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest);
+        }
+    }
+    PathBuf::from(path)
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crate::util::walk::dir_size;
+
+/// What to do when a file with the same name already exists in the destination folder
+#[derive(Clone, Copy, PartialEq)]
+pub enum OnConflict {
+    /// Leave the incoming file where it is
+    Skip,
+    /// Replace the existing file with the incoming one
+    Overwrite,
+    /// Move the incoming file under a new name, e.g. `report (1).pdf`
+    Rename,
+    /// Replace the existing file only if the incoming one was modified more recently
+    KeepNewer,
+    /// Replace the existing file only if the incoming one is larger
+    KeepLarger,
+    /// Remove the incoming file if it is identical to the existing one, otherwise rename it
+    Dedupe,
+}
+
+/// What should happen to a file that is about to be moved
+pub enum Resolution {
+    /// Move the file to this path
+    Move(PathBuf),
+    /// Leave the file where it is
+    Skip,
+    /// The file is identical to the one already at the destination, so it can be removed
+    Duplicate,
+}
+
+/// Work out where a file should be moved to, given that it is about to be moved to `dest`
+pub fn resolve(source: &Path, dest: &Path, policy: OnConflict) -> Result<Resolution, io::Error> {
+    resolve_planned(source, dest, policy, &HashMap::new())
+}
+
+/// Like `resolve`, but for a dry run, in which nothing has been moved yet. `planned` maps each
+/// destination already used by the dry run to the file that would be moved there, and those
+/// files are treated as if they were already in place.
+pub fn resolve_planned(
+    source: &Path,
+    dest: &Path,
+    policy: OnConflict,
+    planned: &HashMap<PathBuf, PathBuf>,
+) -> Result<Resolution, io::Error> {
+    let current = planned.get(dest).map_or(dest, PathBuf::as_path);
+    let existing = match fs::symlink_metadata(current) {
+        Ok(md) => md,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Ok(Resolution::Move(dest.to_path_buf()))
+        }
+        Err(error) => return Err(error),
+    };
+    let incoming = fs::symlink_metadata(source)?;
+
+    let replace_if = |replace: bool| {
+        if replace {
+            Resolution::Move(dest.to_path_buf())
+        } else {
+            Resolution::Skip
+        }
+    };
+    let size = |path: &Path, md: &fs::Metadata| {
+        if md.is_dir() {
+            dir_size(path)
+        } else {
+            md.len()
+        }
+    };
+
+    let resolution = match policy {
+        OnConflict::Skip => Resolution::Skip,
+        OnConflict::Overwrite => Resolution::Move(dest.to_path_buf()),
+        OnConflict::Rename => Resolution::Move(unique_path(dest, planned)),
+        OnConflict::KeepNewer => {
+            let newer = match (incoming.modified(), existing.modified()) {
+                (Ok(i), Ok(e)) => i > e,
+                _ => false,
+            };
+            replace_if(newer)
+        }
+        OnConflict::KeepLarger => replace_if(size(source, &incoming) > size(current, &existing)),
+        OnConflict::Dedupe => {
+            if incoming.is_file() && existing.is_file() && same_contents(source, current)? {
+                Resolution::Duplicate
+            } else {
+                Resolution::Move(unique_path(dest, planned))
+            }
+        }
+    };
+    Ok(resolution)
+}
+
+/// Get a path that does not exist yet (and is not in `planned`) by appending ` (1)`, ` (2)`...
+/// to the file name, before the extension
+fn unique_path(path: &Path, planned: &HashMap<PathBuf, PathBuf>) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default();
+    let ext = path.extension();
+
+    let mut n = 1;
+    loop {
+        let mut name = OsString::from(stem);
+        name.push(format!(" ({n})"));
+        if let Some(ext) = ext {
+            name.push(".");
+            name.push(ext);
+        }
+        let candidate = path.with_file_name(name);
+        if fs::symlink_metadata(&candidate).is_err() && !planned.contains_key(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// Check whether two files have exactly the same contents
+pub fn same_contents(a: &Path, b: &Path) -> Result<bool, io::Error> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+
+    let mut a = BufReader::new(File::open(a)?);
+    let mut b = BufReader::new(File::open(b)?);
+    let mut buf_a = [0; 8192];
+    let mut buf_b = [0; 8192];
+    loop {
+        let n = a.read(&mut buf_a)?;
+        if n == 0 {
+            return Ok(b.read(&mut buf_b)? == 0);
+        }
+        if b.read_exact(&mut buf_b[..n]).is_err() || buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+    }
+}
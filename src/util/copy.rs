@@ -0,0 +1,165 @@
+use std::ffi::OsString;
+use std::fs::{self, File, FileTimes, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::util::conflict::same_contents;
+#[cfg(unix)]
+use crate::util::moved_link_target;
+
+/// Copy a file or folder to `dest`, along with its permissions and timestamps.
+///
+/// Everything is first copied next to `dest` under a temporary name. Once the size of every file
+/// has been checked (and, if `verify` is `true`, its contents compared with the original), the
+/// copy is renamed to `dest`. If anything goes wrong, the partial copy is removed.
+///
+/// With `reflink`, files are cloned so that they share their data with the original until either
+/// is changed. This fails on file systems that do not support it.
+pub fn copy(source: &Path, dest: &Path, verify: bool, reflink: bool) -> Result<(), io::Error> {
+    let part = part_path(dest);
+    // Left behind by an earlier run that was interrupted
+    remove_all(&part);
+
+    // A link with a relative target would point somewhere else from its new folder
+    #[cfg(unix)]
+    let result = match moved_link_target(source, dest) {
+        Some(target) => std::os::unix::fs::symlink(target, &part),
+        None => copy_all(source, &part, verify, reflink),
+    };
+    #[cfg(not(unix))]
+    let result = copy_all(source, &part, verify, reflink);
+
+    let result = result.and_then(|_| fs::rename(&part, dest));
+    if result.is_err() {
+        remove_all(&part);
+    }
+    result
+}
+
+/// Move a file or folder to a different file system, where it cannot simply be renamed.
+///
+/// The file is copied with `copy`, and the original is only removed once the copy is complete.
+/// If the copy fails, the original is left untouched.
+pub fn move_across(source: &Path, dest: &Path, verify: bool) -> Result<(), io::Error> {
+    let md = fs::symlink_metadata(source)?;
+    copy(source, dest, verify, false)?;
+
+    if md.is_dir() {
+        // Some of the original files may already be gone, so the copy has to be kept
+        fs::remove_dir_all(source)
+    } else if let Err(error) = fs::remove_file(source) {
+        let _ = fs::remove_file(dest);
+        Err(error)
+    } else {
+        Ok(())
+    }
+}
+
+/// Get the temporary name used while copying (or linking) to `dest`
+pub(crate) fn part_path(dest: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(dest.file_name().unwrap_or_default());
+    name.push(".cabinet-part");
+    dest.with_file_name(name)
+}
+
+/// Get the temporary name a file is moved to while it is being replaced by another
+pub(crate) fn old_path(dest: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(dest.file_name().unwrap_or_default());
+    name.push(".cabinet-old");
+    dest.with_file_name(name)
+}
+
+/// Remove a file or folder, ignoring any errors
+pub(crate) fn remove_all(path: &Path) {
+    match fs::symlink_metadata(path) {
+        Ok(md) if md.is_dir() => {
+            let _ = fs::remove_dir_all(path);
+        }
+        Ok(_) => {
+            let _ = fs::remove_file(path);
+        }
+        Err(_) => {}
+    }
+}
+
+/// Copy a file, folder or symbolic link, including everything inside a folder
+fn copy_all(source: &Path, dest: &Path, verify: bool, reflink: bool) -> Result<(), io::Error> {
+    let md = fs::symlink_metadata(source)?;
+
+    #[cfg(unix)]
+    if md.is_symlink() {
+        return std::os::unix::fs::symlink(fs::read_link(source)?, dest);
+    }
+
+    if md.is_dir() {
+        fs::create_dir(dest)?;
+        for item in fs::read_dir(source)? {
+            let item = item?;
+            copy_all(&item.path(), &dest.join(item.file_name()), verify, reflink)?;
+        }
+        // Not every platform allows setting the timestamps of a folder
+        if let Ok(dir) = File::open(dest) {
+            let _ = dir.set_times(file_times(&md));
+        }
+        fs::set_permissions(dest, md.permissions())
+    } else if md.is_file() {
+        copy_file(source, dest, &md, verify, reflink)
+    } else {
+        Err(io::Error::other(format!(
+            "\"{}\" is a special file that cannot be copied",
+            source.display()
+        )))
+    }
+}
+
+/// Copy a single file and check that the copy is complete
+fn copy_file(
+    source: &Path,
+    dest: &Path,
+    md: &Metadata,
+    verify: bool,
+    reflink: bool,
+) -> Result<(), io::Error> {
+    let to = if reflink {
+        reflink_copy::reflink(source, dest)?;
+        File::options().write(true).open(dest)?
+    } else {
+        let mut from = File::open(source)?;
+        let mut to = File::create_new(dest)?;
+        io::copy(&mut from, &mut to)?;
+        to
+    };
+    to.sync_all()?;
+
+    if to.metadata()?.len() != md.len() {
+        return Err(io::Error::other(format!(
+            "The copy of \"{}\" is incomplete",
+            source.display()
+        )));
+    }
+    to.set_times(file_times(md))?;
+    drop(to);
+
+    if verify && !same_contents(source, dest)? {
+        return Err(io::Error::other(format!(
+            "The copy of \"{}\" does not match the original",
+            source.display()
+        )));
+    }
+
+    // Set last, as the permissions may not allow writing to the copy
+    fs::set_permissions(dest, md.permissions())
+}
+
+fn file_times(md: &Metadata) -> FileTimes {
+    let mut times = FileTimes::new();
+    if let Ok(t) = md.accessed() {
+        times = times.set_accessed(t);
+    }
+    if let Ok(t) = md.modified() {
+        times = times.set_modified(t);
+    }
+    times
+}
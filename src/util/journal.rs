@@ -0,0 +1,621 @@
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use chrono::{DateTime, Local, Utc};
+
+use crate::util::rename;
+
+/// Header written at the top of every journal file
+const HEADER: &str = "# cabinet journal v1";
+
+/// Get the directory where journals are stored. This is `$XDG_STATE_HOME/cabinet/journal`
+/// (usually `~/.local/state/cabinet/journal`), or the local data directory on platforms
+/// without a state directory.
+pub fn journal_dir() -> Option<PathBuf> {
+    let base = dirs::state_dir().or_else(dirs::data_local_dir)?;
+    Some(base.join("cabinet").join("journal"))
+}
+
+/// Records every folder created and file moved during a single run, so that the run can be
+/// reverted later with `cab undo`.
+///
+/// The journal file is only created once the first entry is recorded, meaning that runs which
+/// do not change anything on disk do not show up in the history. Problems writing the journal
+/// do not stop the run, and are collected in `warnings` instead.
+#[derive(Default)]
+pub struct Journal {
+    file: Option<File>,
+    /// Path of the journal file, once it has been created
+    path: Option<PathBuf>,
+    /// Number of files set aside so far, used to give each one a unique name
+    set_aside: usize,
+    disabled: bool,
+    /// Problems creating or writing the journal, which mean the run cannot be (fully) undone
+    pub warnings: Vec<String>,
+}
+
+impl Journal {
+    pub fn new() -> Journal {
+        Journal::default()
+    }
+
+    /// A journal that records nothing, for runs that do not need to be undone
+    pub fn disabled() -> Journal {
+        Journal {
+            disabled: true,
+            ..Journal::default()
+        }
+    }
+
+    /// Record that `path` was created by this run
+    pub fn record_mkdir(&mut self, path: &Path) {
+        let path = absolute(path);
+        let line = format!("mkdir\t{}\t{}", now(), encode(path.as_os_str()));
+        self.write_line(&line);
+    }
+
+    /// Record that the file at `source` was moved to `destination`. The size and modification
+    /// time of the destination are stored so that later changes can be detected.
+    pub fn record_move(&mut self, source: &Path, destination: &Path) {
+        let (size, mtime) = match fs::symlink_metadata(destination) {
+            Ok(md) => (md.len(), mtime_secs(&md)),
+            Err(_) => (0, 0),
+        };
+        let line = format!(
+            "move\t{}\t{}\t{}\t{}\t{}",
+            now(),
+            encode(absolute(source).as_os_str()),
+            encode(absolute(destination).as_os_str()),
+            size,
+            mtime
+        );
+        self.write_line(&line);
+    }
+
+    /// Record that `destination` was created as a copy or link of `source`, where `action` is
+    /// the name of the action that created it
+    pub fn record_create(&mut self, action: &str, source: &Path, destination: &Path) {
+        let (size, mtime) = match fs::symlink_metadata(destination) {
+            Ok(md) => (md.len(), mtime_secs(&md)),
+            Err(_) => (0, 0),
+        };
+        let line = format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            action,
+            now(),
+            encode(absolute(source).as_os_str()),
+            encode(absolute(destination).as_os_str()),
+            size,
+            mtime
+        );
+        self.write_line(&line);
+    }
+
+    /// Record that the file at `path` was removed because it was identical to `duplicate_of`.
+    /// `md` is the metadata of the removed file, so that its modification time can be restored
+    /// by `cab undo`.
+    pub fn record_remove(&mut self, path: &Path, duplicate_of: &Path, md: &fs::Metadata) {
+        let line = format!(
+            "remove\t{}\t{}\t{}\t{}\t{}",
+            now(),
+            encode(absolute(path).as_os_str()),
+            encode(absolute(duplicate_of).as_os_str()),
+            md.len(),
+            mtime_secs(md)
+        );
+        self.write_line(&line);
+    }
+
+    /// Record that the file or folder at `path` was replaced, and that it was kept at `backup`
+    /// (see [`Journal::set_aside`])
+    pub fn record_replace(&mut self, path: &Path, backup: &Path) {
+        let line = format!(
+            "replace\t{}\t{}\t{}",
+            now(),
+            encode(absolute(path).as_os_str()),
+            encode(absolute(backup).as_os_str())
+        );
+        self.write_line(&line);
+    }
+
+    /// Move a file or folder that is about to be replaced into a folder next to the journal
+    /// file, so that `cab undo` can put it back. Returns where it was moved to, or `None` if
+    /// there is no journal to keep it in.
+    pub fn set_aside(&mut self, path: &Path) -> Result<Option<PathBuf>, io::Error> {
+        if !self.open() {
+            return Ok(None);
+        }
+        let dir = match &self.path {
+            Some(p) => p.with_extension("replaced"),
+            None => return Ok(None),
+        };
+        fs::create_dir_all(&dir)?;
+
+        self.set_aside += 1;
+        let mut name = OsString::from(format!("{}-", self.set_aside));
+        name.push(path.file_name().unwrap_or_default());
+        let backup = dir.join(name);
+        rename(path, &backup, false)?;
+        Ok(Some(backup))
+    }
+
+    /// Create the journal file if it does not exist yet. Returns `false` if the journal is
+    /// disabled.
+    fn open(&mut self) -> bool {
+        if self.disabled {
+            return false;
+        }
+        if self.file.is_none() {
+            match create_journal_file() {
+                Ok((f, path)) => {
+                    self.file = Some(f);
+                    self.path = Some(path);
+                }
+                Err(error) => {
+                    self.warnings.push(format!(
+                        "Unable to create the undo journal, this run cannot be undone:\n{:?}",
+                        error
+                    ));
+                    self.disabled = true;
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if !self.open() {
+            return;
+        }
+
+        let file = self.file.as_mut().unwrap();
+        if let Err(error) = writeln!(file, "{}", line) {
+            self.warnings
+                .push(format!("Unable to write to the undo journal:\n{:?}", error));
+        }
+    }
+}
+
+/// Create a new journal file for the current run and write its header
+fn create_journal_file() -> Result<(File, PathBuf), io::Error> {
+    let dir = match journal_dir() {
+        Some(d) => d,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no state directory is available on this platform",
+            ))
+        }
+    };
+    fs::create_dir_all(&dir)?;
+
+    let started = Local::now();
+    let base_id = started.format("%Y%m%dT%H%M%S").to_string();
+    let mut id = base_id.clone();
+    let mut n = 1;
+
+    let (mut file, path) = loop {
+        let path = dir.join(format!("{id}.log"));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(f) => break (f, path),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                n += 1;
+                id = format!("{base_id}-{n}");
+            }
+            Err(error) => return Err(error),
+        }
+    };
+
+    let cwd = env::current_dir().unwrap_or_default();
+    let command: Vec<String> = env::args_os().map(|a| encode(&a)).collect();
+
+    writeln!(file, "{HEADER}")?;
+    writeln!(file, "run\t{id}")?;
+    writeln!(file, "time\t{}", started.to_rfc3339())?;
+    writeln!(file, "cwd\t{}", encode(cwd.as_os_str()))?;
+    writeln!(file, "command\t{}", command.join("\t"))?;
+    Ok((file, path))
+}
+
+/// A single change made by a run
+pub enum Entry {
+    Mkdir {
+        path: PathBuf,
+    },
+    Move {
+        source: PathBuf,
+        destination: PathBuf,
+        size: u64,
+        mtime: i64,
+    },
+    /// A copy or link of `source`, which is reverted by removing `destination`
+    Create {
+        action: String,
+        source: PathBuf,
+        destination: PathBuf,
+        size: u64,
+        mtime: i64,
+    },
+    /// A duplicate file that was removed, which can be restored by copying `duplicate_of`
+    Remove {
+        path: PathBuf,
+        duplicate_of: PathBuf,
+        size: u64,
+        mtime: i64,
+    },
+    /// A file or folder that was replaced by a sorted file, which was kept at `backup`
+    Replace {
+        path: PathBuf,
+        backup: PathBuf,
+    },
+}
+
+/// A journal that has been read back from disk
+pub struct Run {
+    pub id: String,
+    pub time: Option<DateTime<Local>>,
+    pub cwd: PathBuf,
+    pub command: Vec<String>,
+    pub entries: Vec<Entry>,
+    pub undone: Option<String>,
+    file: PathBuf,
+}
+
+impl Run {
+    /// Number of files moved, copied or linked (or removed as duplicates) by this run
+    pub fn moved_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| !matches!(e, Entry::Mkdir { .. } | Entry::Replace { .. }))
+            .count()
+    }
+
+    /// Display the command line of this run as it would have been typed
+    pub fn command_line(&self) -> String {
+        let mut out: Vec<String> = vec![];
+        for (idx, arg) in self.command.iter().enumerate() {
+            let arg = match idx {
+                // Show the program name rather than the full path to the binary
+                0 => Path::new(arg)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| arg.to_string()),
+                _ => arg.to_string(),
+            };
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                out.push(format!("\"{arg}\""));
+            } else {
+                out.push(arg);
+            }
+        }
+        out.join(" ")
+    }
+
+    /// Mark this run as undone so it is skipped by subsequent calls to `cab undo`
+    pub fn mark_undone(&mut self) -> Result<(), io::Error> {
+        let time = Local::now().to_rfc3339();
+        let mut file = OpenOptions::new().append(true).open(&self.file)?;
+        writeln!(file, "undone\t{time}")?;
+        self.undone = Some(time);
+        Ok(())
+    }
+
+    /// Remove the journal of this run, along with the files and folders it replaced (see
+    /// [`Journal::set_aside`]). The run can no longer be undone.
+    pub fn remove(self) -> Result<(), io::Error> {
+        let replaced = self.file.with_extension("replaced");
+        if replaced.exists() {
+            fs::remove_dir_all(&replaced)?;
+        }
+        fs::remove_file(&self.file)
+    }
+}
+
+/// Read a single journal file
+pub fn load(file: &Path) -> Result<Run, io::Error> {
+    let reader = BufReader::new(File::open(file)?);
+    let mut lines = reader.lines();
+
+    match lines.next() {
+        Some(Ok(header)) if header == HEADER => {}
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a cabinet journal",
+            ))
+        }
+    }
+
+    let mut run = Run {
+        id: String::new(),
+        time: None,
+        cwd: PathBuf::new(),
+        command: vec![],
+        entries: vec![],
+        undone: None,
+        file: file.to_path_buf(),
+    };
+
+    for line in lines {
+        let line = line?;
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields[..] {
+            ["run", id] => run.id = id.to_string(),
+            ["time", t] => {
+                run.time = DateTime::parse_from_rfc3339(t)
+                    .ok()
+                    .map(|d| d.with_timezone(&Local))
+            }
+            ["cwd", p] => run.cwd = PathBuf::from(decode(p)),
+            ["command", ..] => {
+                run.command = fields[1..]
+                    .iter()
+                    .map(|a| decode(a).to_string_lossy().to_string())
+                    .collect()
+            }
+            ["mkdir", _, p] => run.entries.push(Entry::Mkdir {
+                path: PathBuf::from(decode(p)),
+            }),
+            ["move", _, src, dst, size, mtime] => run.entries.push(Entry::Move {
+                source: PathBuf::from(decode(src)),
+                destination: PathBuf::from(decode(dst)),
+                size: size.parse().unwrap_or(0),
+                mtime: mtime.parse().unwrap_or(0),
+            }),
+            [action @ ("copy" | "hardlink" | "symlink" | "reflink"), _, src, dst, size, mtime] => {
+                run.entries.push(Entry::Create {
+                    action: action.to_string(),
+                    source: PathBuf::from(decode(src)),
+                    destination: PathBuf::from(decode(dst)),
+                    size: size.parse().unwrap_or(0),
+                    mtime: mtime.parse().unwrap_or(0),
+                })
+            }
+            ["remove", _, p, dup, size, mtime] => run.entries.push(Entry::Remove {
+                path: PathBuf::from(decode(p)),
+                duplicate_of: PathBuf::from(decode(dup)),
+                size: size.parse().unwrap_or(0),
+                mtime: mtime.parse().unwrap_or(0),
+            }),
+            ["replace", _, p, backup] => run.entries.push(Entry::Replace {
+                path: PathBuf::from(decode(p)),
+                backup: PathBuf::from(decode(backup)),
+            }),
+            ["undone", t] => run.undone = Some(t.to_string()),
+            // Ignore anything unknown, such as a partially written line
+            _ => {}
+        }
+    }
+
+    Ok(run)
+}
+
+/// The runs read by `load_all`
+#[derive(Default)]
+pub struct History {
+    pub runs: Vec<Run>,
+    /// Journals that were skipped because they could not be read
+    pub warnings: Vec<String>,
+}
+
+/// Get all recorded runs, sorted from most to least recent
+pub fn load_all() -> Result<History, io::Error> {
+    let dir = match journal_dir() {
+        Some(d) => d,
+        None => return Ok(History::default()),
+    };
+    if !dir.exists() {
+        return Ok(History::default());
+    }
+
+    let mut runs: Vec<Run> = vec![];
+    let mut warnings: Vec<String> = vec![];
+    for item in fs::read_dir(&dir)? {
+        let item = item?;
+        let path = item.path();
+        if path.extension().and_then(OsStr::to_str) != Some("log") {
+            continue;
+        }
+        match load(&path) {
+            Ok(run) => runs.push(run),
+            Err(error) => warnings.push(format!(
+                "Skipping unreadable journal \"{}\":\n{:?}",
+                path.display(),
+                error
+            )),
+        }
+    }
+
+    runs.sort_by(|a, b| b.time.cmp(&a.time).then_with(|| b.id.cmp(&a.id)));
+    Ok(History { runs, warnings })
+}
+
+/// Get the modification time of a file in seconds since the Unix epoch
+pub fn mtime_secs(md: &fs::Metadata) -> i64 {
+    match md.modified() {
+        Ok(t) => match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        },
+        Err(_) => 0,
+    }
+}
+
+/// Journals always store absolute paths so that runs can be undone from any directory
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339()
+}
+
+/// Escape a path (or argument) so that it fits on a single tab-separated line
+fn encode(s: &OsStr) -> String {
+    let mut out = String::new();
+    for chunk in s.as_encoded_bytes().utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '\t' => out.push_str("\\t"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                c => out.push(c),
+            }
+        }
+        for b in chunk.invalid() {
+            out.push_str(&format!("\\x{b:02x}"));
+        }
+    }
+    out
+}
+
+/// Reverse of [`encode`]
+fn decode(s: &str) -> OsString {
+    let mut bytes: Vec<u8> = vec![];
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('t') => bytes.push(b'\t'),
+            Some('n') => bytes.push(b'\n'),
+            Some('r') => bytes.push(b'\r'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                bytes.push(u8::from_str_radix(&hex, 16).unwrap_or(b'?'));
+            }
+            Some(c) => {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+            None => bytes.push(b'\\'),
+        }
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        OsString::from_vec(bytes)
+    }
+    #[cfg(not(unix))]
+    {
+        OsString::from(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_round_trip() {
+        let cases = [
+            ("plain/path.txt", "plain/path.txt"),
+            ("tab\there", "tab\\there"),
+            ("new\nline\r", "new\\nline\\r"),
+            ("back\\slash", "back\\\\slash"),
+            ("literal \\t", "literal \\\\t"),
+            ("ünïcode ✓", "ünïcode ✓"),
+            ("", ""),
+        ];
+        for (path, encoded) in cases {
+            assert_eq!(encode(OsStr::new(path)), encoded);
+            assert_eq!(decode(encoded), OsString::from(path));
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn encode_round_trip_non_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = OsStr::from_bytes(b"bad\xff\xfe name\t.txt");
+        let encoded = encode(path);
+        assert_eq!(encoded, "bad\\xff\\xfe name\\t.txt");
+        assert_eq!(decode(&encoded), path);
+    }
+
+    #[test]
+    fn load_every_entry() {
+        let file = env::temp_dir().join(format!("cabinet-journal-{}.log", std::process::id()));
+        let tabbed = encode(OsStr::new("/a/tab\tname"));
+        let lines = [
+            HEADER.to_string(),
+            "run\t20240301T093000".to_string(),
+            "time\t2024-03-01T09:30:00+00:00".to_string(),
+            "cwd\t/home/user".to_string(),
+            "command\tcab\tname\t-I\tmy file".to_string(),
+            "mkdir\t2024-03-01T09:30:00+00:00\t/a/out".to_string(),
+            format!("move\t2024-03-01T09:30:00+00:00\t{tabbed}\t/a/out/x\t12\t1709285400"),
+            "copy\t2024-03-01T09:30:00+00:00\t/a/c\t/a/out/c\t3\t-5".to_string(),
+            "symlink\t2024-03-01T09:30:00+00:00\t/a/s\t/a/out/s\t0\t0".to_string(),
+            "remove\t2024-03-01T09:30:00+00:00\t/a/d\t/a/out/d\t7\t100".to_string(),
+            "replace\t2024-03-01T09:30:00+00:00\t/a/out/x\t/j/1-x".to_string(),
+            // Partially written, so ignored
+            "move\t2024-03-01T09:30:00+00:00\t/a/y".to_string(),
+            "undone\t2024-03-02T10:00:00+00:00".to_string(),
+        ];
+        fs::write(&file, lines.join("\n") + "\n").unwrap();
+        let run = load(&file);
+        fs::remove_file(&file).unwrap();
+        let run = run.unwrap();
+
+        assert_eq!(run.id, "20240301T093000");
+        assert_eq!(
+            run.time.map(|t| t.timestamp()),
+            Some(
+                DateTime::parse_from_rfc3339("2024-03-01T09:30:00Z")
+                    .unwrap()
+                    .timestamp()
+            )
+        );
+        assert_eq!(run.cwd, PathBuf::from("/home/user"));
+        assert_eq!(run.command_line(), "cab name -I \"my file\"");
+        assert_eq!(run.undone.as_deref(), Some("2024-03-02T10:00:00+00:00"));
+        assert_eq!(run.entries.len(), 6);
+        assert_eq!(run.moved_count(), 4);
+
+        assert!(matches!(&run.entries[0], Entry::Mkdir { path } if path == Path::new("/a/out")));
+        assert!(matches!(
+            &run.entries[1],
+            Entry::Move { source, destination, size: 12, mtime: 1709285400 }
+                if source == Path::new("/a/tab\tname") && destination == Path::new("/a/out/x")
+        ));
+        assert!(matches!(
+            &run.entries[2],
+            Entry::Create { action, source, destination, size: 3, mtime: -5 }
+                if action == "copy" && source == Path::new("/a/c") && destination == Path::new("/a/out/c")
+        ));
+        assert!(matches!(
+            &run.entries[3],
+            Entry::Create { action, .. } if action == "symlink"
+        ));
+        assert!(matches!(
+            &run.entries[4],
+            Entry::Remove { path, duplicate_of, size: 7, mtime: 100 }
+                if path == Path::new("/a/d") && duplicate_of == Path::new("/a/out/d")
+        ));
+        assert!(matches!(
+            &run.entries[5],
+            Entry::Replace { path, backup }
+                if path == Path::new("/a/out/x") && backup == Path::new("/j/1-x")
+        ));
+    }
+
+    #[test]
+    fn load_rejects_other_files() {
+        let file = env::temp_dir().join(format!("cabinet-not-journal-{}.log", std::process::id()));
+        fs::write(&file, "something else\nrun\tx\n").unwrap();
+        let run = load(&file);
+        fs::remove_file(&file).unwrap();
+        assert!(run.is_err());
+    }
+}
@@ -1,4 +1,10 @@
 pub use self::utils::*;
 
+pub mod action;
+pub mod config;
+pub mod conflict;
+pub mod copy;
+pub mod journal;
 pub mod path;
 pub mod utils;
+pub mod walk;
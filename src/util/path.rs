@@ -1,35 +1,63 @@
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 use dirs;
 
-/// Get the current path where the files will be sorted into. This will also print the current path.
-pub fn get_current_path(path: Option<PathBuf>) -> PathBuf {
-    let paths_parent = path.as_ref().unwrap().display().to_string();
-    println!("CURRENT PATH: {}", &paths_parent);
-    path.unwrap()
+use crate::util::config::Config;
+
+/// Get the path of a template, such as `downloads` for the downloads folder. Template names are
+/// case-insensitive, and templates defined in the config file take precedence over the built-in
+/// templates.
+pub fn template(name: &str, config: &Config) -> Option<PathBuf> {
+    if let Some(p) = config.template(name) {
+        return Some(p);
+    }
+
+    match &name.to_lowercase()[..] {
+        "documents" => dirs::document_dir(),
+        "downloads" => dirs::download_dir(),
+        "desktop" => dirs::desktop_dir(),
+        "home" => dirs::home_dir(),
+        "music" | "audio" => dirs::audio_dir(),
+        "pictures" => dirs::picture_dir(),
+        "videos" | "movies" => dirs::video_dir(),
+        _ => None,
+    }
 }
 
-/// Get filepath object. Only called when it is a valid path or template path.
-pub fn get_path(path: &String, use_template: bool) -> Option<PathBuf> {
-    let mut path_ref: Option<PathBuf> = None;
-
-    if use_template {
-        let path = &path.to_lowercase()[..];
-        match path {
-            "documents" => path_ref = dirs::document_dir(),
-            "downloads" => path_ref = dirs::download_dir(),
-            "desktop" => path_ref = dirs::desktop_dir(),
-            "home" => path_ref = dirs::home_dir(),
-            "music" | "audio" => path_ref = dirs::audio_dir(),
-            "pictures" => path_ref = dirs::picture_dir(),
-            "videos" | "movies" => path_ref = dirs::video_dir(),
-            _ => println!("The template '{}' does not exist.", &path),
+/// Make a path absolute and resolve any `.` and `..` components, without touching the file
+/// system (so symbolic links in the path are not resolved).
+pub fn normalize(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
         }
-    } else if Path::new(path).exists() {
-        path_ref = Some(PathBuf::from(path));
-    } else {
-        println!("Directory \"{}\" either does not exist or this program is missing permissions to access it.", &path);
     }
+    out
+}
 
-    path_ref
+/// Get the relative path that leads from the folder `base` to `path`
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path = normalize(path);
+    let base = normalize(base);
+
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut out = PathBuf::new();
+    for _ in base.components().skip(common) {
+        out.push("..");
+    }
+    for c in path.components().skip(common) {
+        out.push(c);
+    }
+    out
 }
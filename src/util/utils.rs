@@ -1,12 +1,12 @@
-use std::fs::{self, DirEntry};
-use std::io::{self, stdin, stdout, Write};
+use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
-use std::process::exit;
-use std::rc::Rc;
-use std::time::Instant;
 
 use chrono::{DateTime, Utc};
 
+use crate::util::copy;
+use crate::util::path;
+
 // TODO: Use local time instead of UTC
 
 /// Set folder name according to the following format of `Cabinet-YYYYmmddTHHMMSS-<suffix>`
@@ -17,109 +17,39 @@ pub fn set_folder_name(suffix: String) -> String {
     format!("Cabinet-{n}-{suffix}")
 }
 
-/// Create the folder to store the sorted files in. If folder name already exists,
-/// ask user if they wish to proceed or not.
+/// Rename a file or folder. A symbolic link with a relative target is recreated at its new
+/// location so that it still points to the same place.
 ///
-/// If `auto_yes` is `true`, then a folder will be used even if it already exists.
-pub fn create_folder(path: PathBuf, folder: String, auto_yes: bool) -> Result<PathBuf, io::Error> {
-    let mut path = path;
-    path.push(&folder);
-
-    if Path::new(&path).exists() {
-        if auto_yes {
-            return Ok(path);
-        }
-
-        print!("The folder with the name '{folder}' already exists. Sorted files will be placed in this folder anyway. Proceed? [y/N] ");
-        let _ = stdout().flush();
-        let mut ans = String::new();
-        stdin().read_line(&mut ans).expect("Malformed input");
-
-        // could also use .trim_end_matches('\n').trim_end_matches('\r') // ORDER IS IMPORTANT
-        if ans.trim_end() == "y" {
-            println!("\nContinuing anyway...");
-            return Ok(path);
-        } else {
-            println!("\nAborted.");
-            exit(0);
+/// If `dest` is on a different file system, the file is copied and the original is removed
+/// instead (see `copy::move_across`).
+pub fn rename(source: &Path, dest: &Path, verify: bool) -> Result<(), io::Error> {
+    #[cfg(unix)]
+    if let Some(new_target) = moved_link_target(source, dest) {
+        std::os::unix::fs::symlink(&new_target, dest)?;
+        if let Err(error) = fs::remove_file(source) {
+            let _ = fs::remove_file(dest);
+            return Err(error);
         }
+        return Ok(());
     }
 
-    let f = fs::create_dir(&path);
-    match f {
-        Ok(_) => {
-            println!(
-                "New folder '{}' has been created\n --> \"{}\"",
-                &folder,
-                &path.display()
-            );
-            Ok(path)
-        }
-        Err(error) => {
-            println!(
-                "There was a problem creating the folder for \"{}\":\n{:?}",
-                &folder, error
-            );
-            Err(error)
+    match fs::rename(source, dest) {
+        Err(error) if error.kind() == io::ErrorKind::CrossesDevices => {
+            copy::move_across(source, dest, verify)
         }
+        result => result,
     }
 }
 
-// TODO: Consider adding a progress bar
-pub fn sort_files(path: &Path, files: &[DirEntry]) {
-    let mut files_sorted: f64 = 0.0;
-    let start = Instant::now();
-    let mut stdout = stdout();
-
-    for (idx, file) in files.iter().enumerate() {
-        let done = idx as f64 / files.len() as f64;
-        let f = fs::rename(file.path(), path.join(file.file_name()));
-        match f {
-            Ok(_) => files_sorted += 1.0,
-            Err(error) => println!("There was a problem opening the file:\n{:?}", error),
-        }
-
-        print!("\rProcessing {:.1}%", done * 100.0);
-        stdout.flush().unwrap();
+/// If `source` is a symbolic link with a relative target, get the target that a link at `dest`
+/// needs to point to the same place. Returns `None` if `source` is not such a link, or if `dest`
+/// is in the same folder, where the target can stay as it is.
+pub fn moved_link_target(source: &Path, dest: &Path) -> Option<PathBuf> {
+    let target = fs::read_link(source).ok()?;
+    let (from, to) = (source.parent().unwrap_or(Path::new("")), dest.parent());
+    if target.is_absolute() || Some(from) == to {
+        return None;
     }
-
-    let duration = start.elapsed();
-    stdout.flush().unwrap();
-
-    print!("\rProcessed 100%   \n");
-    println!("Time taken: {:?}", duration);
-    println!(
-        "Sorted {}/{} files into folders",
-        &files_sorted,
-        &files.len()
-    );
-}
-
-pub fn sort_files_rc(path: &Path, files: &[Rc<DirEntry>]) {
-    let mut files_sorted: f64 = 0.0;
-    let start = Instant::now();
-    let mut stdout = stdout();
-
-    for (idx, file) in files.iter().enumerate() {
-        let done = idx as f64 / files.len() as f64;
-        let f = fs::rename(file.path(), path.join(file.file_name()));
-        match f {
-            Ok(_) => files_sorted += 1.0,
-            Err(error) => println!("There was a problem opening the file:\n{:?}", error),
-        }
-
-        print!("\rProcessing {:.1}%", done * 100.0);
-        stdout.flush().unwrap();
-    }
-
-    let duration = start.elapsed();
-    stdout.flush().unwrap();
-
-    print!("\rProcessed 100%   \n");
-    println!("Time taken: {:?}", duration);
-    println!(
-        "Sorted {}/{} files into folders",
-        &files_sorted,
-        &files.len()
-    );
+    let to = to.unwrap_or(Path::new(""));
+    Some(path::relative_path(&from.join(&target), to))
 }
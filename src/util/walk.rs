@@ -0,0 +1,279 @@
+use std::borrow::Borrow;
+use std::cell::OnceCell;
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, DirEntry, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::sort::content::{self, ContentType};
+
+/// The kinds of entries that are sorted
+#[derive(Clone, Copy, PartialEq)]
+pub enum Entries {
+    Files,
+    Dirs,
+    All,
+}
+
+impl Entries {
+    /// Symbolic links that are moved as links are treated as files
+    fn includes(&self, md: &Metadata) -> bool {
+        match self {
+            Entries::Files => md.is_file() || md.is_symlink(),
+            Entries::Dirs => md.is_dir(),
+            Entries::All => md.is_file() || md.is_symlink() || md.is_dir(),
+        }
+    }
+}
+
+/// How symbolic links are handled
+#[derive(Clone, Copy, PartialEq)]
+pub enum Symlinks {
+    /// Ignore links
+    Skip,
+    /// Treat links as the file or folder they point to
+    Follow,
+    /// Treat links as files in their own right
+    MoveLink,
+}
+
+/// A file or folder found while walking the directory to sort
+pub struct Entry {
+    path: PathBuf,
+    metadata: Metadata,
+    size: OnceCell<u64>,
+    content_type: OnceCell<Option<&'static ContentType>>,
+}
+
+impl Entry {
+    pub fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    pub fn file_name(&self) -> OsString {
+        self.path.file_name().unwrap_or_default().to_os_string()
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.metadata.is_dir()
+    }
+
+    /// Get the size of the entry in bytes. For a folder this is the total size of everything
+    /// inside it, which is only worked out the first time it is needed.
+    pub fn size(&self) -> u64 {
+        *self.size.get_or_init(|| {
+            if self.is_dir() {
+                dir_size(&self.path)
+            } else {
+                self.metadata.len()
+            }
+        })
+    }
+
+    /// Get the type of a file from its contents, which are only read the first time it is
+    /// needed. Folders, links that are moved as links, and files that cannot be read or whose
+    /// type is not recognised have no content type.
+    pub fn content_type(&self) -> Option<&'static ContentType> {
+        *self.content_type.get_or_init(|| {
+            if !self.metadata.is_file() {
+                return None;
+            }
+            content::detect(&self.path).ok().flatten()
+        })
+    }
+}
+
+/// Get the total size of the files in a folder and all of its subfolders
+pub fn dir_size(path: &Path) -> u64 {
+    let items = match fs::read_dir(path) {
+        Ok(items) => items,
+        Err(_) => return 0,
+    };
+
+    let mut size = 0;
+    for item in items.flatten() {
+        match item.metadata() {
+            Ok(md) if md.is_dir() => size += dir_size(&item.path()),
+            Ok(md) => size += md.len(),
+            Err(_) => {}
+        }
+    }
+    size
+}
+
+/// Options controlling which entries are found when reading the directory to sort
+pub struct WalkOptions {
+    /// Also read the contents of subfolders
+    pub recursive: bool,
+    /// Depth of the shallowest entries to return, where `1` is the directory being sorted
+    pub min_depth: usize,
+    /// Depth of the deepest entries to return, or `None` for no limit
+    pub max_depth: Option<usize>,
+    /// The name of the output folder given with `--output`
+    pub output: Option<String>,
+    /// Whether to return files, folders, or both
+    pub entries: Entries,
+    pub symlinks: Symlinks,
+}
+
+impl Default for WalkOptions {
+    /// Only the files directly inside the directory, skipping symbolic links
+    fn default() -> WalkOptions {
+        WalkOptions {
+            recursive: false,
+            min_depth: 1,
+            max_depth: Some(1),
+            output: None,
+            entries: Entries::Files,
+            symlinks: Symlinks::Skip,
+        }
+    }
+}
+
+/// The entries found by `walk`
+pub struct Walk {
+    pub entries: Vec<Entry>,
+    /// Anything that was skipped because of a problem, such as a folder that could not be read
+    pub warnings: Vec<String>,
+}
+
+/// Read the entries of the directory to sort, as well as its subfolders when walking
+/// recursively. Only the kinds of entries chosen with `--entries` are returned.
+///
+/// Cabinet's own output folders (`Cabinet-...`, the `--output` folder and any folders given in
+/// `skip`) are never entered or returned, so that running a command again does not re-sort
+/// files that have already been sorted.
+///
+/// When following symbolic links, folders that have already been visited are not entered again,
+/// which stops links that point back to one of their parent folders from looping forever.
+pub fn walk(root: &Path, opts: &WalkOptions, skip: &[PathBuf]) -> Walk {
+    let mut skip = skip.to_vec();
+    if let Some(out) = &opts.output {
+        skip.push(root.join(out));
+    }
+
+    // Folder names generated by `util::set_folder_name`
+    let cabinet_folder = Regex::new(r"^Cabinet-\d{8}T\d{6}-").unwrap();
+    let is_cabinet_folder =
+        |name: &OsStr| name.to_str().is_some_and(|n| cabinet_folder.is_match(n));
+
+    let mut entries: Vec<Entry> = vec![];
+    let mut warnings: Vec<String> = vec![];
+    let mut dirs: Vec<(PathBuf, usize)> = vec![(root.to_path_buf(), 1)];
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+    if let Ok(p) = fs::canonicalize(root) {
+        visited.insert(p);
+    }
+
+    while let Some((dir, depth)) = dirs.pop() {
+        let items = match fs::read_dir(&dir) {
+            Ok(items) => items,
+            Err(error) => {
+                warnings.push(format!(
+                    "Unable to read the folder \"{}\", skipping it:\n{:?}",
+                    dir.display(),
+                    error
+                ));
+                continue;
+            }
+        };
+
+        for item in items {
+            let item = match item {
+                Ok(i) => i,
+                Err(_) => continue,
+            };
+            let md = match entry_metadata(&item, opts.symlinks) {
+                Ok(Some(md)) => md,
+                Ok(None) => continue,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                    warnings.push(format!(
+                        "Skipping broken symbolic link \"{}\"",
+                        item.path().display()
+                    ));
+                    continue;
+                }
+                Err(error) => {
+                    warnings.push(format!(
+                        "Unable to read \"{}\", skipping it:\n{:?}",
+                        item.path().display(),
+                        error
+                    ));
+                    continue;
+                }
+            };
+            let is_dir = md.is_dir();
+
+            if is_dir && (is_cabinet_folder(&item.file_name()) || skip.contains(&item.path())) {
+                continue;
+            }
+            if is_dir && opts.recursive && opts.max_depth.is_none_or(|max| depth < max) {
+                let first_visit = opts.symlinks != Symlinks::Follow
+                    || fs::canonicalize(item.path()).is_ok_and(|p| visited.insert(p));
+                if first_visit {
+                    dirs.push((item.path(), depth + 1));
+                } else {
+                    warnings.push(format!(
+                        "Not entering \"{}\" as the folder it links to has already been visited",
+                        item.path().display()
+                    ));
+                }
+            }
+            if depth >= opts.min_depth && opts.entries.includes(&md) {
+                entries.push(Entry {
+                    path: item.path(),
+                    metadata: md,
+                    size: OnceCell::new(),
+                    content_type: OnceCell::new(),
+                });
+            }
+        }
+    }
+
+    Walk { entries, warnings }
+}
+
+/// Get the metadata used to sort an entry, according to how symbolic links are handled.
+/// Returns `None` for links that should be skipped.
+fn entry_metadata(item: &DirEntry, symlinks: Symlinks) -> Result<Option<Metadata>, io::Error> {
+    // This does not follow symbolic links
+    let md = item.metadata()?;
+    if !md.is_symlink() {
+        return Ok(Some(md));
+    }
+
+    match symlinks {
+        Symlinks::Skip => Ok(None),
+        Symlinks::Follow => fs::metadata(item.path()).map(Some),
+        Symlinks::MoveLink => Ok(Some(md)),
+    }
+}
+
+/// Remove any entries that are inside a folder which is itself being moved, as they will be
+/// moved along with the folder
+pub fn remove_nested<T: Borrow<Entry>>(entries: Vec<T>) -> Vec<T> {
+    let dirs: Vec<PathBuf> = entries
+        .iter()
+        .map(Borrow::<Entry>::borrow)
+        .filter(|e| e.is_dir())
+        .map(|e| e.path())
+        .collect();
+    if dirs.is_empty() {
+        return entries;
+    }
+
+    entries
+        .into_iter()
+        .filter(|e| {
+            let path = &Borrow::<Entry>::borrow(e).path;
+            !dirs.iter().any(|d| path != d && path.starts_with(d))
+        })
+        .collect()
+}